@@ -0,0 +1,49 @@
+use brainfuck_toy::bench::{self, default_programs};
+use brainfuck_toy::engine::{Engine, Options};
+use brainfuck_toy::interpreter_it;
+
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+/// 解析和生成机器码的时间
+fn compile(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compile");
+    for program in default_programs() {
+        group.throughput(Throughput::Bytes(program.code.len() as u64));
+        group.bench_with_input(BenchmarkId::new("it", &program.name), &program.code, |b, code| {
+            b.iter(|| interpreter_it::parse(code, false))
+        });
+        #[cfg(any(all(target_os = "linux", target_arch = "x86_64"), target_arch = "aarch64"))]
+        group.bench_with_input(BenchmarkId::new("jit", &program.name), &program.code, |b, code| {
+            b.iter(|| brainfuck_toy::Interpreter::default().machine_code(code.clone()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+/// 各引擎执行整个程序的时间，吞吐量按执行的中间表指令数计算。
+/// naive 执行一次 mandelbrot.bf 要将近一分钟，不在这里测量，需要时用 bf bench --engine naive
+fn execute(c: &mut Criterion) {
+    for program in default_programs() {
+        let instructions = bench::instructions(&program).unwrap();
+        let mut group = c.benchmark_group(program.name.as_str());
+        group.sample_size(10).measurement_time(Duration::from_secs(30)).throughput(Throughput::Elements(instructions));
+        for engine in Engine::all().into_iter().filter(|engine| *engine != Engine::Naive) {
+            group.bench_function(engine.to_string(), |b| {
+                b.iter(|| {
+                    let options = Options {
+                        input: Some(Box::new(std::io::Cursor::new(program.input.clone()))),
+                        output: Some(Box::new(std::io::sink())),
+                        ..Options::default()
+                    };
+                    engine.run(program.code.clone(), options).unwrap()
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, compile, execute);
+criterion_main!(benches);
@@ -0,0 +1,31 @@
+#![no_main]
+
+use brainfuck_toy::differential;
+use brainfuck_toy::interpreter_it;
+use brainfuck_toy::limit::{Aborted, Limits};
+use brainfuck_toy_fuzz::{Program, STEP_LIMIT};
+
+use libfuzzer_sys::fuzz_target;
+
+const JIT_TAPE: usize = 65536; // JIT 纸带的长度
+
+// 在步数限制内结束、没有越过 JIT 纸带的程序，所有引擎的输出、纸带、指针和是否出错都要一致
+fuzz_target!(|program: Program| {
+    let source = program.source();
+    let mut interpreter = interpreter_it::Interpreter::default()
+        .with_limits(Limits::default().max_steps(STEP_LIMIT))
+        .with_input(std::io::Cursor::new(program.input.clone()))
+        .with_output(std::io::sink());
+    if let Err(e) = interpreter.run(source.clone()) {
+        if e.is::<Aborted>() {
+            return;
+        }
+    }
+    if interpreter.tape().len() > JIT_TAPE {
+        return;
+    }
+
+    if let Err(e) = differential::check(&source, &program.input, &differential::Options::default()) {
+        panic!("{}\nprogram: {}", e, String::from_utf8_lossy(&source));
+    }
+});
@@ -0,0 +1,61 @@
+#![no_main]
+
+use brainfuck_toy::limit::Limits;
+use brainfuck_toy_fuzz::{Program, STEP_LIMIT};
+
+use libfuzzer_sys::fuzz_target;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+
+const GUARD: usize = 4096; // 每块内存前后保护区的大小
+const CANARY: u8 = 0xa5;
+
+/// 在每块内存前后加上填满 CANARY 的保护区，释放时检查保护区没有被改写，
+/// 用来发现机器码写到纸带以外的内存。机器码没有插桩，AddressSanitizer 看不到它的访问
+struct Guarded;
+
+impl Guarded {
+    fn outer(layout: Layout) -> (usize, Layout) {
+        let pad = GUARD.max(layout.align());
+        (pad, Layout::from_size_align(layout.size() + 2 * pad, layout.align()).unwrap())
+    }
+}
+
+unsafe impl GlobalAlloc for Guarded {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let (pad, outer) = Self::outer(layout);
+        let base = System.alloc(outer);
+        if base.is_null() {
+            return base;
+        }
+        std::ptr::write_bytes(base, CANARY, pad);
+        std::ptr::write_bytes(base.add(pad + layout.size()), CANARY, pad);
+        base.add(pad)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (pad, outer) = Self::outer(layout);
+        let base = ptr.sub(pad);
+        let before = std::slice::from_raw_parts(base, pad);
+        let after = std::slice::from_raw_parts(ptr.add(layout.size()), pad);
+        if before.iter().chain(after).any(|c| *c != CANARY) {
+            // 分配器里不能 panic，直接中止，libFuzzer 会保存这个输入
+            std::process::abort();
+        }
+        System.dealloc(base, outer);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Guarded = Guarded;
+
+// JIT 执行括号配对的程序，指针停在纸带内，纸带前后的内存不被改写
+fuzz_target!(|program: Program| {
+    let mut interpreter = brainfuck_toy::Interpreter::default()
+        .with_limits(Limits::default().max_steps(STEP_LIMIT))
+        .with_input(std::io::Cursor::new(program.input.clone()))
+        .with_output(std::io::sink());
+    let _ = interpreter.run(program.source());
+    assert!(interpreter.pointer() < interpreter.tape().len());
+    // interpreter 在这里释放，Guarded 检查纸带前后的保护区
+});
@@ -0,0 +1,72 @@
+#![no_main]
+
+use brainfuck_toy::formatter::Formatter;
+use brainfuck_toy::interpreter;
+use brainfuck_toy::interpreter_it::{self, ItOpcode};
+use brainfuck_toy::jit;
+use brainfuck_toy::limit::{Aborted, Limits};
+use brainfuck_toy::opcode;
+
+use libfuzzer_sys::fuzz_target;
+
+// 任意字节作为源码: 解析不能 panic，各个 Code::from 对括号不配对的源码都要拒绝，对其余源码都要接受
+fuzz_target!(|data: &[u8]| {
+    for debug in [false, true] {
+        let parsed = interpreter_it::parse(data, debug);
+        assert_eq!(parsed.opcodes.len(), parsed.spans.len());
+        assert!(parsed.unmatched.windows(2).all(|w| w[0] < w[1]));
+        for (pc, opcode) in parsed.opcodes.iter().enumerate() {
+            assert!(opcode::is_opcode(data[parsed.spans[pc].start], debug));
+            if let ItOpcode::RSB(lsb) = *opcode {
+                assert_eq!(parsed.opcodes[lsb as usize], ItOpcode::LSB(pc as u32 - 1));
+            }
+        }
+        let balanced = parsed.unmatched.is_empty();
+
+        // interpreter 没有单独的解析接口，执行 0 步时只会解析
+        let mut naive = interpreter::Interpreter::default()
+            .with_limits(Limits::default().max_steps(0))
+            .with_input(std::io::empty())
+            .with_output(std::io::sink());
+        if debug {
+            naive = naive.with_debug(0);
+        }
+        let naive = match naive.run(data.to_vec()) {
+            Ok(()) => true,
+            Err(e) => e.is::<Aborted>(),
+        };
+        assert_eq!(naive, balanced);
+
+        let mut it = interpreter_it::Interpreter::default();
+        if debug {
+            it = it.with_debug(0);
+        }
+        assert_eq!(it.load(data.to_vec()).is_ok(), balanced);
+
+        assert_eq!(jit::Code::from(data.to_vec(), debug).is_ok(), balanced);
+        #[cfg(any(all(target_os = "linux", target_arch = "x86_64"), target_arch = "aarch64"))]
+        {
+            let mut machine = brainfuck_toy::Interpreter::default();
+            if debug {
+                machine = machine.with_debug(0);
+            }
+            assert_eq!(machine.machine_code(data.to_vec()).is_ok(), balanced);
+        }
+    }
+
+    // 格式化只改变指令之间的空白和注释，再次格式化结果不变
+    let opcodes = |data: &[u8], debug: bool| opcode::opcodes(data, debug).map(|(_, u)| u).collect::<Vec<u8>>();
+    for (formatter, debug) in [
+        (Formatter::default(), false),
+        (Formatter::default().with_width(1).with_comments(false), false),
+        (Formatter::default().with_comments(false).with_debug(true), true),
+    ] {
+        match formatter.format(data) {
+            Ok(formatted) => {
+                assert_eq!(opcodes(formatted.as_bytes(), debug), opcodes(data, debug));
+                assert_eq!(formatter.format(formatted.as_bytes()).unwrap(), formatted);
+            }
+            Err(_) => assert!(!interpreter_it::parse(data, false).unmatched.is_empty()),
+        }
+    }
+});
@@ -0,0 +1,21 @@
+#![no_main]
+
+use brainfuck_toy::engine::{Engine, Options};
+use brainfuck_toy::limit::Limits;
+use brainfuck_toy_fuzz::{Program, STEP_LIMIT};
+
+use libfuzzer_sys::fuzz_target;
+
+// 括号配对的程序在每个引擎上带步数限制执行: 可以出错，但不能 panic
+fuzz_target!(|program: Program| {
+    let source = program.source();
+    for engine in Engine::all() {
+        let options = Options {
+            limits: Limits::default().max_steps(STEP_LIMIT),
+            input: Some(Box::new(std::io::Cursor::new(program.input.clone()))),
+            output: Some(Box::new(std::io::sink())),
+            ..Options::default()
+        };
+        let _ = engine.run(source.clone(), options);
+    }
+});
@@ -0,0 +1,55 @@
+//! 模糊测试共用的程序生成器
+
+use arbitrary::Arbitrary;
+
+/// 有执行限制的测试中，每个程序最多执行的指令数
+pub const STEP_LIMIT: u64 = 100_000;
+
+/// 生成程序用的指令，移动和加减按次数展开成连续的指令
+#[derive(Debug, Arbitrary)]
+pub enum Instruction {
+    Left(u8),
+    Right(u8),
+    Add(u8),
+    Sub(u8),
+    Open,
+    Close,
+    Read,
+    Write,
+}
+
+/// 括号配对的程序和它的输入
+#[derive(Debug, Arbitrary)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    pub input: Vec<u8>,
+}
+
+impl Program {
+    /// 生成源码: 丢掉没有 [ 可以配对的 ]，最后补上还没有关闭的 ]
+    pub fn source(&self) -> Vec<u8> {
+        let mut source = Vec::new();
+        let mut depth = 0;
+        for instruction in &self.instructions {
+            match *instruction {
+                Instruction::Left(n) => source.extend(std::iter::repeat_n(b'<', n as usize + 1)),
+                Instruction::Right(n) => source.extend(std::iter::repeat_n(b'>', n as usize + 1)),
+                Instruction::Add(n) => source.extend(std::iter::repeat_n(b'+', n as usize + 1)),
+                Instruction::Sub(n) => source.extend(std::iter::repeat_n(b'-', n as usize + 1)),
+                Instruction::Open => {
+                    depth += 1;
+                    source.push(b'[');
+                }
+                Instruction::Close if depth > 0 => {
+                    depth -= 1;
+                    source.push(b']');
+                }
+                Instruction::Close => {}
+                Instruction::Read => source.push(b','),
+                Instruction::Write => source.push(b'.'),
+            }
+        }
+        source.extend(std::iter::repeat_n(b']', depth));
+        source
+    }
+}
@@ -0,0 +1,132 @@
+use super::engine::{Engine, Options};
+use super::interpreter_it;
+
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+const LIFE_GENERATIONS: usize = 10; // 默认基准中 life.bf 演化的代数
+
+/// 基准测试的程序
+pub struct Program {
+    pub name: String,
+    pub code: Vec<u8>,
+    pub input: Vec<u8>, // , 的输入，每次执行都从头读取
+}
+
+impl Program {
+    pub fn new(name: impl Into<String>, code: Vec<u8>, input: Vec<u8>) -> Self {
+        Self { name: name.into(), code, input }
+    }
+}
+
+/// 默认的基准程序: bf/mandelbrot.bf，以及放入一个滑翔机后演化 LIFE_GENERATIONS 代的 bf/life.bf
+pub fn default_programs() -> Vec<Program> {
+    let mut life_input = b"ab\nbc\nca\ncb\ncc\n".to_vec();
+    life_input.extend(std::iter::repeat_n(b'\n', LIFE_GENERATIONS));
+    life_input.extend_from_slice(b"q\n");
+
+    vec![
+        Program::new("mandelbrot.bf", include_bytes!("../bf/mandelbrot.bf").to_vec(), Vec::new()),
+        Program::new("life.bf", include_bytes!("../bf/life.bf").to_vec(), life_input),
+    ]
+}
+
+/// 一个程序在一个引擎上的测量结果
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub program: String,
+    pub engine: Engine,
+    pub runs: usize,
+    pub compile: Duration, // 最短的解析和编译时间
+    pub execute: Duration, // 最短的执行时间
+    pub mean: Duration,    // 平均每次的总时间
+    pub instructions: u64, // 执行的中间表指令数，ADD(10) 算一条，各引擎相同
+    pub speedup: f64,      // 第一个引擎的平均总时间是这个引擎的多少倍
+}
+
+/// 用 interpreter_it 执行一次程序，返回执行的中间表指令数
+pub fn instructions(program: &Program) -> Result<u64, Box<dyn std::error::Error>> {
+    let mut interpreter = interpreter_it::Interpreter::default()
+        .with_input(std::io::Cursor::new(program.input.clone()))
+        .with_output(std::io::sink());
+    interpreter.run(program.code.clone())?;
+    Ok(interpreter.steps())
+}
+
+/// 在每个引擎上执行程序 runs 次，输出丢弃。速度比以 engines 中的第一个引擎为基准
+pub fn measure(program: &Program, engines: &[Engine], runs: usize) -> Result<Vec<Measurement>, Box<dyn std::error::Error>> {
+    if runs == 0 {
+        return Err("runs must be positive".into());
+    }
+    let instructions = instructions(program)?;
+
+    let mut measurements: Vec<Measurement> = Vec::new();
+    for &engine in engines {
+        let mut timings = Vec::new();
+        for _ in 0..runs {
+            let options = Options {
+                input: Some(Box::new(std::io::Cursor::new(program.input.clone()))),
+                output: Some(Box::new(std::io::sink())),
+                ..Options::default()
+            };
+            timings.push(engine.run_timed(program.code.clone(), options).map_err(|e| format!("{} on {}: {}", program.name, engine, e))?);
+        }
+
+        let mean = timings.iter().map(|t| t.compile + t.execute).sum::<Duration>() / runs as u32;
+        let baseline = measurements.first().map_or(mean, |m| m.mean);
+        measurements.push(Measurement {
+            program: program.name.clone(),
+            engine,
+            runs,
+            compile: timings.iter().map(|t| t.compile).min().unwrap_or_default(),
+            execute: timings.iter().map(|t| t.execute).min().unwrap_or_default(),
+            mean,
+            instructions,
+            speedup: baseline.as_secs_f64() / mean.as_secs_f64().max(f64::MIN_POSITIVE),
+        });
+    }
+    Ok(measurements)
+}
+
+/// 表格形式的报告
+pub fn report(measurements: &[Measurement]) -> String {
+    let width = measurements.iter().map(|m| m.program.len()).max().unwrap_or(0).max("program".len());
+    let mut report = format!(
+        "{:<width$}  {:<6}  {:>12}  {:>12}  {:>12}  {:>14}  {:>8}\n",
+        "program", "engine", "compile", "execute", "mean", "instructions", "speedup"
+    );
+    for m in measurements {
+        report += &format!(
+            "{:<width$}  {:<6}  {:>12}  {:>12}  {:>12}  {:>14}  {:>7.2}x\n",
+            m.program,
+            m.engine,
+            format!("{:.3?}", m.compile),
+            format!("{:.3?}", m.execute),
+            format!("{:.3?}", m.mean),
+            m.instructions,
+            m.speedup
+        );
+    }
+    report
+}
+
+/// JSON 形式的报告，时间以纳秒为单位，便于比较不同版本的结果
+pub fn to_json(measurements: &[Measurement]) -> Value {
+    let results: Vec<Value> = measurements
+        .iter()
+        .map(|m| {
+            json!({
+                "program": m.program,
+                "engine": m.engine.to_string(),
+                "runs": m.runs,
+                "compile_ns": m.compile.as_nanos() as u64,
+                "execute_ns": m.execute.as_nanos() as u64,
+                "mean_ns": m.mean.as_nanos() as u64,
+                "instructions": m.instructions,
+                "speedup": m.speedup,
+            })
+        })
+        .collect();
+    json!({ "version": env!("CARGO_PKG_VERSION"), "results": results })
+}
@@ -0,0 +1,134 @@
+use super::debugger::SourceMap;
+use super::profile::{LoopProfile, Profile};
+
+use std::io::prelude::*;
+
+/// 源码覆盖率: 每个源码字节被执行的次数
+pub struct Coverage {
+    source: Vec<u8>,
+    hits: Vec<Option<u64>>, // 不是指令的字节为 None
+    loops: Vec<LoopProfile>,
+    loop_positions: Vec<(usize, usize)>, // 每个循环的 [ 的行列号
+    source_map: SourceMap,
+}
+
+impl Coverage {
+    /// profile 没有源码位置时 (从快照恢复) 所有字节都为 None
+    pub fn new(source: Vec<u8>, profile: &Profile) -> Self {
+        let mut hits = vec![None; source.len()];
+        for (pc, count) in profile.counts().iter().enumerate() {
+            let Some(span) = profile.span(pc) else {
+                continue;
+            };
+            // 合并的指令之间可能有注释，只统计和第一个字节相同的指令字节
+            let Some(first) = source.get(span.start).copied() else {
+                continue;
+            };
+            for i in span.filter(|i| *i < source.len()) {
+                if source[i] == first {
+                    hits[i] = Some(*count);
+                }
+            }
+        }
+
+        let source_map = SourceMap::new(&source);
+        let loops: Vec<LoopProfile> = profile.loops().into_iter().filter(|l| profile.span(l.lsb).is_some()).collect();
+        let loop_positions = loops
+            .iter()
+            .filter_map(|l| profile.span(l.lsb))
+            .map(|span| source_map.position(span.start))
+            .collect();
+
+        Self { source, hits, loops, loop_positions, source_map }
+    }
+
+    /// 每个源码字节的执行次数，不是指令的字节为 None
+    pub fn hits(&self) -> &[Option<u64>] {
+        &self.hits
+    }
+
+    /// 第 line 行 (从 1 开始) 的执行次数，取这一行指令执行次数的最大值，没有指令时为 None
+    pub fn line_hits(&self, line: usize) -> Option<u64> {
+        let range = self.source_map.line(line)?;
+        self.hits[range].iter().flatten().copied().max()
+    }
+
+    /// 行数，不包括最后一个换行符之后的空行
+    fn line_count(&self) -> usize {
+        let newlines = self.source.iter().filter(|c| **c == b'\n').count();
+        if self.source.ends_with(b"\n") { newlines } else { newlines + 1 }
+    }
+
+    /// 带执行次数的源码，没有执行过的指令下面用 ^ 标出，最后列出没有进入过的循环
+    pub fn annotate<W: Write>(&self, mut out: W) -> std::io::Result<()> {
+        for line in 1..=self.line_count() {
+            let Some(range) = self.source_map.line(line) else {
+                continue;
+            };
+            let text = String::from_utf8_lossy(&self.source[range.clone()]);
+            match self.line_hits(line) {
+                Some(hits) => writeln!(out, "{:>12} | {}", hits, text)?,
+                None => writeln!(out, "{:>12} | {}", "-", text)?,
+            }
+
+            let marks: String = self.hits[range]
+                .iter()
+                .map(|hit| if *hit == Some(0) { '^' } else { ' ' })
+                .collect();
+            if marks.contains('^') {
+                writeln!(out, "{:>12} | {}", "", marks.trim_end())?;
+            }
+        }
+
+        let total = self.hits.iter().flatten().count();
+        let executed = self.hits.iter().flatten().filter(|hit| **hit > 0).count();
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{} of {} instructions executed ({:.1}%)",
+            executed,
+            total,
+            executed as f64 * 100.0 / total.max(1) as f64
+        )?;
+        let never: Vec<String> = self.loops
+            .iter()
+            .zip(&self.loop_positions)
+            .filter(|(l, _)| l.iterations == 0)
+            .map(|(_, (line, column))| format!("{}:{}", line, column))
+            .collect();
+        if !never.is_empty() {
+            writeln!(out, "Loops never entered: {}", never.join(", "))?;
+        }
+        Ok(())
+    }
+
+    /// lcov 格式，path 是 SF 中的源码路径。每个循环记为一个函数，进入循环体的次数为调用次数
+    pub fn lcov<W: Write>(&self, path: &str, mut out: W) -> std::io::Result<()> {
+        writeln!(out, "TN:")?;
+        writeln!(out, "SF:{}", path)?;
+
+        for (line, column) in &self.loop_positions {
+            writeln!(out, "FN:{},loop@{}:{}", line, line, column)?;
+        }
+        for (l, (line, column)) in self.loops.iter().zip(&self.loop_positions) {
+            writeln!(out, "FNDA:{},loop@{}:{}", l.iterations, line, column)?;
+        }
+        writeln!(out, "FNF:{}", self.loops.len())?;
+        writeln!(out, "FNH:{}", self.loops.iter().filter(|l| l.iterations > 0).count())?;
+
+        let mut found = 0;
+        let mut hit = 0;
+        for line in 1..=self.line_count() {
+            if let Some(hits) = self.line_hits(line) {
+                writeln!(out, "DA:{},{}", line, hits)?;
+                found += 1;
+                if hits > 0 {
+                    hit += 1;
+                }
+            }
+        }
+        writeln!(out, "LF:{}", found)?;
+        writeln!(out, "LH:{}", hit)?;
+        writeln!(out, "end_of_record")
+    }
+}
@@ -0,0 +1,420 @@
+use super::debugger::{unescape, Breakpoint, Debugger, Stop};
+use super::limit::Limits;
+use super::protocol::{read_message, write_message, InvalidMessage};
+
+use std::io::prelude::*;
+use std::sync::mpsc;
+
+use serde_json::{json, Value};
+
+const RUN_SLICE: u64 = 1 << 14; // 继续执行时每执行这么多步检查一次有没有新的请求
+const THREAD_ID: u64 = 1; // 只有一个线程
+const TAPE_REFERENCE: u64 = 1; // Tape 作用域
+const STATE_REFERENCE: u64 = 2; // State 作用域
+
+/// 通过 input/output 提供 Debug Adapter Protocol 服务，行列号从 1 开始。
+/// 断点按源码行设置，纸带作为变量作用域，循环栈作为调用栈，调试控制台中输入的内容作为程序的输入
+pub fn serve<R, W>(input: R, output: W) -> Result<(), Box<dyn std::error::Error>>
+where
+    R: BufRead + Send + 'static,
+    W: Write,
+{
+    // 在单独的线程中读取请求，执行程序时也能处理 pause
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut input = input;
+        loop {
+            let message = match read_message(&mut input) {
+                Ok(Some(message)) => message,
+                // 跳过无效的消息
+                Err(e) if e.is::<InvalidMessage>() => continue,
+                _ => break,
+            };
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut session = Session::new(output);
+    loop {
+        let message = if session.running.is_some() {
+            match receiver.try_recv() {
+                Ok(message) => Some(message),
+                Err(mpsc::TryRecvError::Empty) => None,
+                Err(mpsc::TryRecvError::Disconnected) => return Ok(()),
+            }
+        } else {
+            match receiver.recv() {
+                Ok(message) => Some(message),
+                Err(_) => return Ok(()),
+            }
+        };
+
+        if let Some(message) = message {
+            if !session.handle(&message)? {
+                return Ok(());
+            }
+        }
+        session.run_slice()?;
+    }
+}
+
+/// 一次调试会话
+struct Session<W: Write> {
+    out: W,
+    seq: u64,
+    debugger: Option<Debugger>,
+    path: String,
+    lines: Vec<(usize, Option<usize>)>, // 客户端设置的断点
+    stop_on_entry: bool,
+    configured: bool,               // 收到了 configurationDone
+    running: Option<Option<usize>>, // 正在执行，值为要停在的指令
+    stopped: Option<Stop>,          // 单步等同步执行的结果，发送响应之后再报告
+    paused: bool,                   // pause 打断了执行
+}
+
+impl<W: Write> Session<W> {
+    fn new(out: W) -> Self {
+        Self {
+            out,
+            seq: 0,
+            debugger: None,
+            path: String::new(),
+            lines: Vec::new(),
+            stop_on_entry: false,
+            configured: false,
+            running: None,
+            stopped: None,
+            paused: false,
+        }
+    }
+
+    fn send(&mut self, mut message: Value) -> std::io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        write_message(&mut self.out, &message)
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> std::io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    /// 处理一条消息，返回 false 时结束会话
+    fn handle(&mut self, message: &Value) -> Result<bool, Box<dyn std::error::Error>> {
+        if message["type"] != "request" {
+            return Ok(true);
+        }
+        let command = message["command"].as_str().unwrap_or_default().to_string();
+        let args = &message["arguments"];
+
+        let result = self.request(&command, args);
+        let response = match &result {
+            Ok(body) => json!({
+                "type": "response",
+                "request_seq": message["seq"],
+                "success": true,
+                "command": command,
+                "body": body,
+            }),
+            Err(e) => json!({
+                "type": "response",
+                "request_seq": message["seq"],
+                "success": false,
+                "command": command,
+                "message": e.to_string(),
+            }),
+        };
+        self.send(response)?;
+
+        if let Some(stop) = self.stopped.take() {
+            self.report(stop)?;
+        }
+        match command.as_str() {
+            "launch" if result.is_ok() => {
+                self.event("initialized", json!({}))?;
+                self.start()?;
+            }
+            "configurationDone" => self.start()?,
+            "pause" if self.paused => {
+                self.paused = false;
+                self.event("stopped", json!({ "reason": "pause", "threadId": THREAD_ID, "allThreadsStopped": true }))?;
+            }
+            "disconnect" | "terminate" => return Ok(false),
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    fn debugger(&mut self) -> Result<&mut Debugger, Box<dyn std::error::Error>> {
+        self.debugger.as_mut().ok_or_else(|| "No program launched".into())
+    }
+
+    /// 处理请求，返回响应的 body
+    fn request(&mut self, command: &str, args: &Value) -> Result<Value, Box<dyn std::error::Error>> {
+        match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsStepBack": true,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => {
+                self.path = args["program"].as_str().ok_or("Missing program")?.to_string();
+                let mut debugger = Debugger::new(std::fs::read(&self.path)?)?;
+                if let Some(input) = args["input"].as_str() {
+                    debugger.feed(&unescape(input)?);
+                }
+                if let Some(max_steps) = args["maxSteps"].as_u64() {
+                    debugger = debugger.with_limits(Limits::default().max_steps(max_steps));
+                }
+                self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+                self.debugger = Some(debugger);
+                self.apply_breakpoints();
+                Ok(json!({}))
+            }
+            "setBreakpoints" => {
+                self.lines = args["breakpoints"]
+                    .as_array()
+                    .map(|breakpoints| {
+                        breakpoints
+                            .iter()
+                            .filter_map(|b| {
+                                let line = b["line"].as_u64()? as usize;
+                                Some((line, b["column"].as_u64().map(|column| column as usize)))
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                Ok(json!({ "breakpoints": self.apply_breakpoints() }))
+            }
+            "configurationDone" => {
+                self.configured = true;
+                Ok(json!({}))
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "stackTrace" => Ok(self.stack_trace()?),
+            "scopes" => {
+                let tape_len = self.debugger()?.interpreter().tape().len();
+                Ok(json!({ "scopes": [
+                    { "name": "Tape", "variablesReference": TAPE_REFERENCE, "indexedVariables": tape_len, "expensive": false },
+                    { "name": "State", "variablesReference": STATE_REFERENCE, "expensive": false },
+                ] }))
+            }
+            "variables" => Ok(self.variables(args)?),
+            "continue" => {
+                self.running = Some(None);
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" => {
+                match self.debugger()?.step_over_end() {
+                    Some(end) => self.running = Some(Some(end)),
+                    None => self.step(|debugger| debugger.step())?,
+                }
+                Ok(json!({}))
+            }
+            "stepIn" => {
+                self.step(|debugger| debugger.step())?;
+                Ok(json!({}))
+            }
+            "stepOut" => {
+                self.running = Some(self.debugger()?.step_out_end());
+                Ok(json!({}))
+            }
+            "stepBack" => {
+                self.step(|debugger| debugger.reverse_step())?;
+                Ok(json!({}))
+            }
+            "reverseContinue" => {
+                self.step(|debugger| debugger.reverse_continue())?;
+                Ok(json!({}))
+            }
+            "pause" => {
+                self.paused = self.running.take().is_some();
+                Ok(json!({}))
+            }
+            "evaluate" => {
+                // 调试控制台中输入的内容作为程序的输入，支持 \n 等转义
+                let data = unescape(args["expression"].as_str().unwrap_or_default())?;
+                self.debugger()?.feed(&data);
+                Ok(json!({ "result": format!("fed {} bytes", data.len()), "variablesReference": 0 }))
+            }
+            "disconnect" | "terminate" => Ok(json!({})),
+            _ => Err(format!("Unsupported command {}", command).into()),
+        }
+    }
+
+    /// 开始执行，需要先收到 launch 和 configurationDone
+    fn start(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if !self.configured || self.debugger.is_none() {
+            return Ok(());
+        }
+        if self.stop_on_entry {
+            self.event("stopped", json!({ "reason": "entry", "threadId": THREAD_ID, "allThreadsStopped": true }))?;
+        } else {
+            self.running = Some(None);
+        }
+        Ok(())
+    }
+
+    /// 把客户端设置的断点加到调试器，返回 setBreakpoints 响应中的断点。
+    /// 行断点只停在这一行的第一条指令，否则单行的程序每一步都会停下
+    fn apply_breakpoints(&mut self) -> Vec<Value> {
+        let Some(debugger) = &mut self.debugger else {
+            return self.lines.iter().map(|(line, _)| json!({ "verified": false, "line": line })).collect();
+        };
+        debugger.clear_breakpoints();
+        self.lines
+            .iter()
+            .map(|(line, column)| {
+                let source = Breakpoint::Source { line: *line, column: *column };
+                let Some(pc) = debugger.breakpoint_pcs(source).first().copied() else {
+                    return json!({ "verified": false, "line": line });
+                };
+                let id = debugger.add_breakpoint(Breakpoint::Instruction(pc));
+                let (line, column) = debugger.position(pc).unwrap_or((*line, 1));
+                json!({ "id": id, "verified": true, "line": line, "column": column })
+            })
+            .collect()
+    }
+
+    /// 同步执行单步等操作，然后报告停止的原因
+    fn step<F>(&mut self, f: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut Debugger) -> Result<Stop, Box<dyn std::error::Error>>,
+    {
+        self.running = None;
+        self.stopped = Some(f(self.debugger()?)?);
+        Ok(())
+    }
+
+    /// 继续执行时每次执行一段
+    fn run_slice(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(end) = self.running else {
+            return Ok(());
+        };
+        let stop = match self.debugger()?.run_until(end, RUN_SLICE) {
+            Ok(stop) => stop,
+            Err(e) => {
+                // 程序出错时结束调试会话，继续处理请求
+                self.running = None;
+                self.flush_output()?;
+                self.event("output", json!({ "category": "stderr", "output": format!("{}\n", e) }))?;
+                return Ok(self.event("terminated", json!({}))?);
+            }
+        };
+        self.flush_output()?;
+        if let Some(stop) = stop {
+            self.running = None;
+            self.report(stop)?;
+        }
+        Ok(())
+    }
+
+    /// 程序的输出作为 output 事件发送
+    fn flush_output(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let output = self.debugger()?.interpreter_mut().take_output();
+        if !output.is_empty() {
+            let text = String::from_utf8_lossy(&output).into_owned();
+            self.event("output", json!({ "category": "stdout", "output": text }))?;
+        }
+        Ok(())
+    }
+
+    fn report(&mut self, stop: Stop) -> Result<(), Box<dyn std::error::Error>> {
+        self.flush_output()?;
+        let (reason, description, hit) = match stop {
+            Stop::Step => ("step", None, None),
+            Stop::Breakpoint(id) => ("breakpoint", None, Some(id)),
+            Stop::Watchpoint(hit) => ("data breakpoint", Some(format!("cell {} {} -> {}", hit.cell, hit.old, hit.new)), None),
+            Stop::Marker => ("pause", Some("Paused at #".to_string()), None),
+            Stop::NeedsInput => {
+                self.event("output", json!({
+                    "category": "console",
+                    "output": "Waiting for input, type it in the debug console\n",
+                }))?;
+                ("pause", Some("Waiting for input".to_string()), None)
+            }
+            Stop::Beginning => ("step", Some("At the beginning of the program".to_string()), None),
+            Stop::Halted => {
+                self.event("exited", json!({ "exitCode": 0 }))?;
+                return Ok(self.event("terminated", json!({}))?);
+            }
+        };
+
+        let mut body = json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true });
+        if let Some(description) = description {
+            body["description"] = json!(description);
+        }
+        if let Some(id) = hit {
+            body["hitBreakpointIds"] = json!([id]);
+        }
+        Ok(self.event("stopped", body)?)
+    }
+
+    /// 调用栈: 当前指令在最上面，下面是从内到外的循环
+    fn stack_trace(&mut self) -> Result<Value, Box<dyn std::error::Error>> {
+        let name = std::path::Path::new(&self.path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let source = json!({ "name": name, "path": self.path });
+        let debugger = self.debugger()?;
+        let interpreter = debugger.interpreter();
+        let pc = interpreter.pc();
+
+        let (line, column) = debugger.position(pc).unwrap_or_else(|| {
+            // 执行结束时指向源码末尾
+            debugger.source_map().position(debugger.source().len().saturating_sub(1))
+        });
+        let current = match interpreter.opcodes().get(pc) {
+            Some(opcode) => format!("{:?}", opcode),
+            None => "<end of program>".to_string(),
+        };
+        let mut frames = vec![json!({ "id": 0, "name": current, "line": line, "column": column, "source": source })];
+
+        for (i, lsb) in debugger.loop_stack().into_iter().rev().enumerate() {
+            let (line, column) = debugger.position(lsb).unwrap_or((0, 0));
+            frames.push(json!({
+                "id": i + 1,
+                "name": format!("loop {}:{}", line, column),
+                "line": line,
+                "column": column,
+                "source": source,
+            }));
+        }
+
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    fn variables(&mut self, args: &Value) -> Result<Value, Box<dyn std::error::Error>> {
+        let debugger = self.debugger()?;
+        let interpreter = debugger.interpreter();
+
+        let variables: Vec<Value> = match args["variablesReference"].as_u64() {
+            Some(TAPE_REFERENCE) => {
+                let tape = interpreter.tape();
+                let start = args["start"].as_u64().unwrap_or(0) as usize;
+                let count = args["count"].as_u64().map_or(tape.len(), |count| count as usize);
+                tape.iter()
+                    .enumerate()
+                    .skip(start)
+                    .take(count)
+                    .map(|(i, c)| {
+                        let name = if i == interpreter.pointer() { format!("{} (pointer)", i) } else { i.to_string() };
+                        let ascii = if c.is_ascii_graphic() { format!(" '{}'", *c as char) } else { String::new() };
+                        json!({ "name": name, "value": format!("{} 0x{:02x}{}", c, c, ascii), "variablesReference": 0 })
+                    })
+                    .collect()
+            }
+            Some(STATE_REFERENCE) => vec![
+                json!({ "name": "instruction", "value": debugger.describe(interpreter.pc()), "variablesReference": 0 }),
+                json!({ "name": "pointer", "value": interpreter.pointer().to_string(), "variablesReference": 0 }),
+                json!({ "name": "steps", "value": interpreter.steps().to_string(), "variablesReference": 0 }),
+                json!({ "name": "input", "value": format!("{} bytes", interpreter.input().len()), "variablesReference": 0 }),
+            ],
+            _ => vec![],
+        };
+
+        Ok(json!({ "variables": variables }))
+    }
+}
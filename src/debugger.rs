@@ -0,0 +1,814 @@
+use super::format_tape;
+use super::interpreter_it::{Frame, Interpreter, ItOpcode, State};
+use super::limit::Limits;
+
+use std::collections::{HashMap, VecDeque};
+use std::io::prelude::*;
+
+const TAPE_RADIUS: usize = 8; // tape 命令默认显示指针左右各多少个单元
+const UNDO_LIMIT: usize = 1 << 20; // 撤销记录最多保存多少步，每步 24 字节，一共约 24 MiB
+const CHECKPOINT_INTERVAL: u64 = 1 << 16; // 最初每执行多少步保存一个检查点
+const MAX_CHECKPOINTS: usize = 64; // 检查点超过这个数量时隔一个删一个，间隔加倍，每个检查点复制一份纸带
+
+/// 断点
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    Instruction(usize), // 中间表指令下标
+    Source { line: usize, column: Option<usize> }, // 源码位置，从 1 开始，没有列号时对整行生效
+}
+
+/// 观察点的触发条件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Watch {
+    Write,     // 任意写入，包括写入相同的值
+    Value(u8), // 写入指定的值
+    Zero,      // 由非零变为零
+}
+
+/// 纸带单元上的观察点
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub cell: usize,
+    pub watch: Watch,
+}
+
+impl Watchpoint {
+    fn matches(&self, old: u8, new: u8) -> bool {
+        match self.watch {
+            Watch::Write => true,
+            Watch::Value(v) => new == v,
+            Watch::Zero => old != 0 && new == 0,
+        }
+    }
+}
+
+/// 观察点命中时的写入
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hit {
+    pub id: usize, // 观察点编号
+    pub pc: usize, // 写入单元的指令
+    pub cell: usize,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// 停止执行的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Step,              // 单步执行完成
+    Breakpoint(usize), // 命中断点，值为断点编号
+    Watchpoint(Hit),   // 写入了观察的单元，此时写入的指令已经执行
+    Marker,            // 执行了源码中的 # 调试指令
+    Beginning,         // 反向执行到了程序开头
+    NeedsInput,        // 需要输入
+    Halted,            // 执行结束
+}
+
+/// 源码偏移与行列号的对应关系
+pub struct SourceMap {
+    line_starts: Vec<usize>, // 每行第一个字节的偏移
+    len: usize,
+}
+
+impl SourceMap {
+    pub fn new(source: &[u8]) -> Self {
+        let mut line_starts = vec![0];
+        for (i, c) in source.iter().enumerate() {
+            if *c == b'\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        Self { line_starts, len: source.len() }
+    }
+
+    /// 偏移对应的行列号，从 1 开始
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        (line, offset - self.line_starts[line - 1] + 1)
+    }
+
+    /// 行列号对应的偏移
+    pub fn offset(&self, line: usize, column: usize) -> Option<usize> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self.line_starts.get(line).copied().unwrap_or(self.len);
+        let offset = start + column.checked_sub(1)?;
+        if offset < end { Some(offset) } else { None }
+    }
+
+    /// 某一行在源码中的范围，不包括换行符
+    pub fn line(&self, line: usize) -> Option<std::ops::Range<usize>> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self.line_starts.get(line).map_or(self.len, |next| next - 1);
+        Some(start..end)
+    }
+}
+
+/// 撤销一条指令需要的信息
+struct Undo {
+    pc: usize,
+    pointer: usize,
+    old: u8,           // 执行前指针指向的单元的值
+    input: Option<u8>, // , 读取的输入
+}
+
+/// 检查点，反向执行超出撤销记录时从这里重新执行
+struct Checkpoint {
+    steps: u64,
+    fed: usize, // 保存时已经提供的输入字节数
+    frame: Frame,
+}
+
+/// 基于 interpreter_it 的单步调试器。
+/// 正向执行时记录撤销信息并定期保存检查点，可以反向单步、反向执行到断点和跳到第 N 步。
+/// 反向执行不会撤销已经输出的内容。
+/// 执行历史占用的内存有上限: 撤销记录约 24 MiB，加上最多 MAX_CHECKPOINTS 份纸带和未读取输入的副本
+pub struct Debugger {
+    interpreter: Interpreter,
+    source: Vec<u8>,
+    source_map: SourceMap,
+    breakpoints: Vec<Option<Breakpoint>>, // 下标即断点编号，删除后为 None
+    breakpoint_pcs: HashMap<usize, usize>, // 指令下标 -> 断点编号
+    watchpoints: Vec<Option<Watchpoint>>, // 下标即观察点编号，删除后为 None
+    undo: VecDeque<Undo>, // 最近执行的指令的撤销信息
+    checkpoints: Vec<Checkpoint>, // 按步数排序，第一个是程序开头
+    checkpoint_interval: u64,
+    inputs: Vec<u8>, // 通过 feed 提供的所有输入
+    limits: Limits,  // 解释器的执行限制
+}
+
+impl Debugger {
+    /// 源码中的 # 作为调试指令，执行到 # 时暂停
+    pub fn new(source: Vec<u8>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut interpreter = Interpreter::default().with_debug(TAPE_RADIUS);
+        interpreter.load(source.clone())?;
+        let source_map = SourceMap::new(&source);
+        let checkpoints = vec![Checkpoint { steps: 0, fed: 0, frame: interpreter.frame() }];
+
+        Ok(Self {
+            interpreter,
+            source,
+            source_map,
+            breakpoints: Vec::new(),
+            breakpoint_pcs: HashMap::new(),
+            watchpoints: Vec::new(),
+            undo: VecDeque::new(),
+            checkpoints,
+            checkpoint_interval: CHECKPOINT_INTERVAL,
+            inputs: Vec::new(),
+            limits: Limits::default(),
+        })
+    }
+
+    /// 设置最大指令数、超时时间和取消句柄，超出时单步和继续执行返回 limit::Aborted
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.interpreter = std::mem::take(&mut self.interpreter).with_limits(limits.clone());
+        self.limits = limits;
+        self
+    }
+
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    /// 直接修改解释器的状态后，反向执行的结果不再可靠
+    pub fn interpreter_mut(&mut self) -> &mut Interpreter {
+        &mut self.interpreter
+    }
+
+    pub fn source(&self) -> &[u8] {
+        &self.source
+    }
+
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    /// 重新从头执行，保留断点和观察点，丢弃输入和执行记录
+    pub fn restart(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.interpreter = Interpreter::default().with_debug(TAPE_RADIUS).with_limits(self.limits.clone());
+        self.interpreter.load(self.source.clone())?;
+        self.undo.clear();
+        self.checkpoints = vec![Checkpoint { steps: 0, fed: 0, frame: self.interpreter.frame() }];
+        self.checkpoint_interval = CHECKPOINT_INTERVAL;
+        self.inputs.clear();
+        Ok(())
+    }
+
+    /// 提供输入，反向执行后重新执行时会再次读取
+    pub fn feed(&mut self, data: &[u8]) {
+        self.inputs.extend_from_slice(data);
+        self.interpreter.feed(data);
+    }
+
+    /// 添加断点，返回断点编号
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+        self.breakpoints.push(Some(breakpoint));
+        self.resolve_breakpoints();
+        self.breakpoints.len() - 1
+    }
+
+    pub fn remove_breakpoint(&mut self, id: usize) -> bool {
+        let removed = self.breakpoints.get_mut(id).and_then(|b| b.take()).is_some();
+        self.resolve_breakpoints();
+        removed
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+        self.breakpoint_pcs.clear();
+    }
+
+    /// 所有断点及其编号
+    pub fn breakpoints(&self) -> impl Iterator<Item = (usize, Breakpoint)> + '_ {
+        self.breakpoints.iter().enumerate().filter_map(|(id, b)| b.map(|b| (id, b)))
+    }
+
+    /// 断点对应的指令下标
+    pub fn breakpoint_pcs(&self, breakpoint: Breakpoint) -> Vec<usize> {
+        let opcode_len = self.interpreter.opcodes().len();
+        match breakpoint {
+            Breakpoint::Instruction(pc) if pc < opcode_len => vec![pc],
+            Breakpoint::Instruction(_) => vec![],
+            Breakpoint::Source { line, column: None } => {
+                let Some(range) = self.source_map.line(line) else {
+                    return vec![];
+                };
+                (0..opcode_len)
+                    .filter(|pc| self.span_start(*pc).is_some_and(|start| range.contains(&start)))
+                    .collect()
+            }
+            Breakpoint::Source { line, column: Some(column) } => {
+                let Some(offset) = self.source_map.offset(line, column) else {
+                    return vec![];
+                };
+                // 包含该位置的指令，位置在注释中时取后面的第一条指令
+                (0..opcode_len)
+                    .find(|pc| self.interpreter.span(*pc).is_some_and(|span| span.end > offset))
+                    .into_iter()
+                    .collect()
+            }
+        }
+    }
+
+    /// 添加观察点，返回观察点编号
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+        self.watchpoints.push(Some(watchpoint));
+        self.watchpoints.len() - 1
+    }
+
+    pub fn remove_watchpoint(&mut self, id: usize) -> bool {
+        self.watchpoints.get_mut(id).and_then(|w| w.take()).is_some()
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    /// 所有观察点及其编号
+    pub fn watchpoints(&self) -> impl Iterator<Item = (usize, Watchpoint)> + '_ {
+        self.watchpoints.iter().enumerate().filter_map(|(id, w)| w.map(|w| (id, w)))
+    }
+
+    fn resolve_breakpoints(&mut self) {
+        let mut breakpoint_pcs = HashMap::new();
+        for (id, breakpoint) in self.breakpoints() {
+            for pc in self.breakpoint_pcs(breakpoint) {
+                breakpoint_pcs.entry(pc).or_insert(id);
+            }
+        }
+        self.breakpoint_pcs = breakpoint_pcs;
+    }
+
+    fn span_start(&self, pc: usize) -> Option<usize> {
+        self.interpreter.span(pc).map(|span| span.start)
+    }
+
+    /// 指令在源码中的行列号
+    pub fn position(&self, pc: usize) -> Option<(usize, usize)> {
+        self.span_start(pc).map(|start| self.source_map.position(start))
+    }
+
+    /// 当前指令所在的循环，从外到内，值为 [ 的指令下标
+    pub fn loop_stack(&self) -> Vec<usize> {
+        let mut stack = Vec::new();
+        for (pc, opcode) in self.interpreter.opcodes().iter().enumerate().take(self.interpreter.pc()) {
+            match opcode {
+                ItOpcode::LSB(_) => stack.push(pc),
+                ItOpcode::RSB(_) => {
+                    stack.pop();
+                }
+                _ => {}
+            }
+        }
+        stack
+    }
+
+    fn stop(&self, state: State) -> Option<Stop> {
+        match state {
+            State::Halted => Some(Stop::Halted),
+            State::NeedsInput => Some(Stop::NeedsInput),
+            State::Debug => Some(Stop::Marker),
+            State::Running => self.breakpoint_pcs.get(&self.interpreter.pc()).map(|id| Stop::Breakpoint(*id)),
+        }
+    }
+
+    /// 只有 + - , 会写入指针指向的单元
+    fn writes(&self, pc: usize) -> bool {
+        matches!(
+            self.interpreter.opcodes().get(pc),
+            Some(ItOpcode::ADD(_) | ItOpcode::SUB(_) | ItOpcode::GETCHAR)
+        )
+    }
+
+    /// 第 pc 条指令把 cell 从 old 改成 new 时命中的观察点
+    fn watch(&self, pc: usize, cell: usize, old: u8, new: u8) -> Option<Stop> {
+        if !self.writes(pc) {
+            return None;
+        }
+        self.watchpoints()
+            .find(|(_, w)| w.cell == cell && w.matches(old, new))
+            .map(|(id, _)| Stop::Watchpoint(Hit { id, pc, cell, old, new }))
+    }
+
+    /// 执行一条指令，记录撤销信息，需要时保存检查点
+    fn forward(&mut self) -> Result<State, Box<dyn std::error::Error>> {
+        let pc = self.interpreter.pc();
+        let pointer = self.interpreter.pointer();
+        let old = self.interpreter.tape()[pointer];
+        let steps = self.interpreter.steps();
+        let state = self.interpreter.step()?;
+        if self.interpreter.steps() == steps {
+            return Ok(state);
+        }
+
+        let input = (self.interpreter.opcodes()[pc] == ItOpcode::GETCHAR).then(|| self.interpreter.tape()[pointer]);
+        self.undo.push_back(Undo { pc, pointer, old, input });
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.pop_front();
+        }
+
+        let last = self.checkpoints.last().map_or(0, |c| c.steps);
+        if steps + 1 >= last + self.checkpoint_interval {
+            self.checkpoints.push(Checkpoint {
+                steps: steps + 1,
+                fed: self.inputs.len(),
+                frame: self.interpreter.frame(),
+            });
+            if self.checkpoints.len() > MAX_CHECKPOINTS {
+                // 保留第一个，隔一个删一个
+                let mut i = 0;
+                self.checkpoints.retain(|_| {
+                    i += 1;
+                    i % 2 == 1
+                });
+                self.checkpoint_interval *= 2;
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// 执行一条指令，返回需要停止的原因
+    fn step_checked(&mut self) -> Result<Option<Stop>, Box<dyn std::error::Error>> {
+        let pc = self.interpreter.pc();
+        let cell = self.interpreter.pointer();
+        let old = self.interpreter.tape()[cell];
+        let steps = self.interpreter.steps();
+        let state = self.forward()?;
+
+        if self.interpreter.steps() != steps {
+            if let Some(stop) = self.watch(pc, cell, old, self.interpreter.tape()[cell]) {
+                return Ok(Some(stop));
+            }
+        }
+
+        Ok(self.stop(state))
+    }
+
+    /// 执行一条指令
+    pub fn step(&mut self) -> Result<Stop, Box<dyn std::error::Error>> {
+        Ok(self.step_checked()?.unwrap_or(Stop::Step))
+    }
+
+    /// 最多执行 budget 条指令，遇到断点、观察点、需要输入或者结束时返回停止的原因，
+    /// 执行到第 end 条指令之前返回 Stop::Step，否则返回 None
+    pub fn run_until(&mut self, end: Option<usize>, budget: u64) -> Result<Option<Stop>, Box<dyn std::error::Error>> {
+        for _ in 0..budget {
+            if let Some(stop) = self.step_checked()? {
+                return Ok(Some(stop));
+            }
+            if end == Some(self.interpreter.pc()) {
+                return Ok(Some(Stop::Step));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 当前指令是 [ 时执行完整个循环要停在的位置: ] 的下一条指令
+    pub fn step_over_end(&self) -> Option<usize> {
+        match self.interpreter.opcodes().get(self.interpreter.pc()) {
+            Some(ItOpcode::LSB(v)) => Some(*v as usize + 2),
+            _ => None,
+        }
+    }
+
+    /// 执行完当前所在的循环要停在的位置: 最内层循环的 ] 的下一条指令
+    pub fn step_out_end(&self) -> Option<usize> {
+        match self.interpreter.opcodes().get(*self.loop_stack().last()?) {
+            Some(ItOpcode::LSB(v)) => Some(*v as usize + 2),
+            _ => None,
+        }
+    }
+
+    /// 当前指令是 [ 时执行完整个循环，否则执行一条指令
+    pub fn step_over(&mut self) -> Result<Stop, Box<dyn std::error::Error>> {
+        match self.step_over_end() {
+            Some(end) => Ok(self.run_until(Some(end), u64::MAX)?.unwrap_or(Stop::Step)),
+            None => self.step(),
+        }
+    }
+
+    /// 执行完当前所在的循环，不在循环中时执行到结束
+    pub fn step_out(&mut self) -> Result<Stop, Box<dyn std::error::Error>> {
+        let end = self.step_out_end();
+        Ok(self.run_until(end, u64::MAX)?.unwrap_or(Stop::Step))
+    }
+
+    /// 执行到断点、观察点、需要输入或者结束
+    pub fn continue_execution(&mut self) -> Result<Stop, Box<dyn std::error::Error>> {
+        Ok(self.run_until(None, u64::MAX)?.unwrap_or(Stop::Step))
+    }
+
+    /// 回到第 steps 步。
+    /// 从之前最近的检查点重新执行，最后最多 UNDO_LIMIT 步记录撤销信息，重新执行产生的输出被丢弃
+    fn seek(&mut self, steps: u64) -> Result<(), Box<dyn std::error::Error>> {
+        let index = self.checkpoints.partition_point(|c| c.steps < steps).saturating_sub(1);
+        let checkpoint = &self.checkpoints[index];
+        let output_len = self.interpreter.output().len();
+        self.interpreter.set_frame(&checkpoint.frame);
+        self.interpreter.feed(&self.inputs[checkpoint.fed..]);
+        self.undo.clear();
+
+        let start = steps.saturating_sub(UNDO_LIMIT as u64).max(checkpoint.steps);
+        while self.interpreter.steps() < start {
+            let state = self.interpreter.run_for(start - self.interpreter.steps())?;
+            if matches!(state, State::Halted | State::NeedsInput) {
+                return Err("Execution history diverged".into());
+            }
+        }
+        while self.interpreter.steps() < steps {
+            let state = self.forward()?;
+            if matches!(state, State::Halted | State::NeedsInput) {
+                return Err("Execution history diverged".into());
+            }
+        }
+
+        self.interpreter.truncate_output(output_len);
+        Ok(())
+    }
+
+    /// 撤销一条指令，返回撤销后需要停止的原因
+    fn backward(&mut self) -> Result<Option<Stop>, Box<dyn std::error::Error>> {
+        let steps = self.interpreter.steps();
+        if steps == 0 {
+            return Ok(Some(Stop::Beginning));
+        }
+        if self.undo.is_empty() {
+            self.seek(steps)?;
+        }
+        let Some(undo) = self.undo.pop_back() else {
+            return Err("No execution history".into());
+        };
+
+        let new = self.interpreter.tape()[undo.pointer];
+        self.interpreter.rewind(undo.pc, undo.pointer, undo.old, undo.input);
+        if let Some(stop) = self.watch(undo.pc, undo.pointer, undo.old, new) {
+            return Ok(Some(stop));
+        }
+        Ok(self.breakpoint_pcs.get(&undo.pc).map(|id| Stop::Breakpoint(*id)))
+    }
+
+    /// 反向执行一条指令
+    pub fn reverse_step(&mut self) -> Result<Stop, Box<dyn std::error::Error>> {
+        Ok(self.backward()?.unwrap_or(Stop::Step))
+    }
+
+    /// 反向执行到断点、观察点的写入指令之前或者程序开头
+    pub fn reverse_continue(&mut self) -> Result<Stop, Box<dyn std::error::Error>> {
+        loop {
+            if let Some(stop) = self.backward()? {
+                return Ok(stop);
+            }
+        }
+    }
+
+    /// 跳到第 steps 步，不检查断点和观察点。
+    /// 向前执行时遇到需要输入或者结束会提前停止
+    pub fn goto(&mut self, steps: u64) -> Result<Stop, Box<dyn std::error::Error>> {
+        let current = self.interpreter.steps();
+        if steps < current {
+            if current - steps <= self.undo.len() as u64 {
+                while self.interpreter.steps() > steps {
+                    self.backward()?;
+                }
+            } else {
+                self.seek(steps)?;
+            }
+            return Ok(Stop::Step);
+        }
+
+        while self.interpreter.steps() < steps {
+            match self.forward()? {
+                State::Halted => return Ok(Stop::Halted),
+                State::NeedsInput => return Ok(Stop::NeedsInput),
+                _ => {}
+            }
+        }
+        Ok(Stop::Step)
+    }
+
+    /// 描述第 pc 条指令: 下标、指令、行列号和源码
+    pub fn describe(&self, pc: usize) -> String {
+        let Some(opcode) = self.interpreter.opcodes().get(pc) else {
+            return format!("#{} <end of program>", pc);
+        };
+        match self.interpreter.span(pc) {
+            Some(span) => {
+                let (line, column) = self.source_map.position(span.start);
+                let text = String::from_utf8_lossy(&self.source[span]);
+                let text: String = text.chars().filter(|c| !c.is_whitespace()).take(32).collect();
+                format!("#{} {:?} at {}:{}  {}", pc, opcode, line, column, text)
+            }
+            None => format!("#{} {:?}", pc, opcode),
+        }
+    }
+
+    /// 指针左右各 radius 个单元
+    pub fn tape_window(&self, radius: usize) -> String {
+        format_tape(self.interpreter.tape(), self.interpreter.pointer(), radius)
+    }
+
+    /// 命令行调试界面，从 input 读取命令，调试信息和程序输出写到 out
+    pub fn repl<R: BufRead, W: Write>(&mut self, input: R, mut out: W) -> Result<(), Box<dyn std::error::Error>> {
+        writeln!(out, "{}", self.describe(self.interpreter.pc()))?;
+        write!(out, "(bf) ")?;
+        out.flush()?;
+
+        for line in input.lines() {
+            let line = line?;
+            let mut words = line.trim().splitn(2, ' ');
+            let command = words.next().unwrap_or("");
+            let arg = words.next().unwrap_or("").trim();
+
+            if matches!(command, "q" | "quit") {
+                break;
+            }
+            let result = self.command(command, arg, &mut out);
+
+            let output = self.interpreter.take_output();
+            if !output.is_empty() {
+                out.write_all(&output)?;
+                writeln!(out)?;
+            }
+
+            // 命令出错时输出错误，继续等待下一个命令
+            let stop = match result {
+                Ok(stop) => stop,
+                Err(e) => {
+                    writeln!(out, "error: {}", e)?;
+                    None
+                }
+            };
+            match stop {
+                Some(Stop::Breakpoint(id)) => {
+                    writeln!(out, "Breakpoint {}: {}", id, self.describe(self.interpreter.pc()))?;
+                }
+                Some(Stop::Watchpoint(hit)) => {
+                    writeln!(out, "Watchpoint {}: cell {} {} -> {} by {}", hit.id, hit.cell, hit.old, hit.new, self.describe(hit.pc))?;
+                    writeln!(out, "{}", self.describe(self.interpreter.pc()))?;
+                }
+                Some(Stop::Marker) => {
+                    writeln!(out, "Paused at #: {}", self.describe(self.interpreter.pc()))?;
+                    writeln!(out, "{}", self.tape_window(TAPE_RADIUS))?;
+                }
+                Some(Stop::NeedsInput) => {
+                    writeln!(out, "Waiting for input, use `input TEXT`: {}", self.describe(self.interpreter.pc()))?;
+                }
+                Some(Stop::Beginning) => {
+                    writeln!(out, "At the beginning of the program: {}", self.describe(self.interpreter.pc()))?;
+                }
+                Some(Stop::Halted) => writeln!(out, "Program halted after {} steps", self.interpreter.steps())?,
+                Some(Stop::Step) => writeln!(out, "{}", self.describe(self.interpreter.pc()))?,
+                None => {}
+            }
+
+            write!(out, "(bf) ")?;
+            out.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// 执行 repl 中的一个命令，返回执行停下的原因，不执行程序的命令返回 None
+    fn command<W: Write>(&mut self, command: &str, arg: &str, out: &mut W) -> Result<Option<Stop>, Box<dyn std::error::Error>> {
+        let stop = match command {
+            "" => None,
+            "s" | "step" => {
+                let n = if arg.is_empty() { 1 } else { parse_number(arg, "step [N]")? };
+                let mut stop = Stop::Step;
+                for _ in 0..n {
+                    stop = self.step()?;
+                    if stop != Stop::Step {
+                        break;
+                    }
+                }
+                Some(stop)
+            }
+            "n" | "next" => Some(self.step_over()?),
+            "finish" => Some(self.step_out()?),
+            "c" | "continue" => Some(self.continue_execution()?),
+            "rs" | "reverse-step" => {
+                let n = if arg.is_empty() { 1 } else { parse_number(arg, "reverse-step [N]")? };
+                let mut stop = Stop::Step;
+                for _ in 0..n {
+                    stop = self.reverse_step()?;
+                    if stop != Stop::Step {
+                        break;
+                    }
+                }
+                Some(stop)
+            }
+            "rc" | "reverse-continue" => Some(self.reverse_continue()?),
+            "goto" => Some(self.goto(parse_number(arg, "goto N")?)?),
+            "b" | "break" => {
+                match parse_breakpoint(arg) {
+                    Some(breakpoint) => {
+                        let id = self.add_breakpoint(breakpoint);
+                        let pcs = self.breakpoint_pcs(breakpoint);
+                        writeln!(out, "Breakpoint {} at {:?} ({} instructions)", id, breakpoint, pcs.len())?;
+                    }
+                    None => writeln!(out, "Usage: break LINE[:COLUMN] | break #INDEX")?,
+                }
+                None
+            }
+            "d" | "delete" => {
+                if arg.is_empty() {
+                    self.clear_breakpoints();
+                } else if !self.remove_breakpoint(parse_number(arg, "delete [ID]")?) {
+                    writeln!(out, "No breakpoint {}", arg)?;
+                }
+                None
+            }
+            "watch" => {
+                match parse_watchpoint(arg) {
+                    Some(watchpoint) => {
+                        let id = self.add_watchpoint(watchpoint);
+                        writeln!(out, "Watchpoint {} on cell {} ({:?})", id, watchpoint.cell, watchpoint.watch)?;
+                    }
+                    None => writeln!(out, "Usage: watch CELL [VALUE|zero]")?,
+                }
+                None
+            }
+            "unwatch" => {
+                if arg.is_empty() {
+                    self.clear_watchpoints();
+                } else if !self.remove_watchpoint(parse_number(arg, "unwatch [ID]")?) {
+                    writeln!(out, "No watchpoint {}", arg)?;
+                }
+                None
+            }
+            "watchpoints" => {
+                for (id, watchpoint) in self.watchpoints() {
+                    writeln!(out, "{}: cell {} ({:?})", id, watchpoint.cell, watchpoint.watch)?;
+                }
+                None
+            }
+            "info" | "breakpoints" => {
+                for (id, breakpoint) in self.breakpoints() {
+                    writeln!(out, "{}: {:?}", id, breakpoint)?;
+                }
+                None
+            }
+            "w" | "where" => {
+                writeln!(out, "{}", self.describe(self.interpreter.pc()))?;
+                writeln!(out, "pointer {}, steps {}", self.interpreter.pointer(), self.interpreter.steps())?;
+                None
+            }
+            "t" | "tape" => {
+                let radius = if arg.is_empty() { TAPE_RADIUS } else { parse_number(arg, "tape [RADIUS]")? };
+                writeln!(out, "{}", self.tape_window(radius))?;
+                None
+            }
+            "l" | "loops" => {
+                for pc in self.loop_stack() {
+                    writeln!(out, "{}", self.describe(pc))?;
+                }
+                None
+            }
+            "input" => {
+                self.feed(&unescape(arg)?);
+                None
+            }
+            "restart" => {
+                self.restart()?;
+                writeln!(out, "{}", self.describe(self.interpreter.pc()))?;
+                None
+            }
+            "h" | "help" => {
+                writeln!(out, "{}", HELP)?;
+                None
+            }
+            _ => {
+                writeln!(out, "Unknown command {:?}, try help", command)?;
+                None
+            }
+        };
+        Ok(stop)
+    }
+}
+
+const HELP: &str = "\
+step [N]          (s) execute N instructions
+next              (n) execute the whole loop starting at the current [
+finish                run until the innermost loop exits
+continue          (c) run until a breakpoint, watchpoint, input or the end
+reverse-step [N]  (rs) undo N instructions
+reverse-continue  (rc) run backwards until a breakpoint, a watched write or the beginning
+goto N                go to step N
+break LINE[:COL]  (b) break at a source position
+break #INDEX      (b) break at an instruction index
+delete [ID]       (d) delete one or all breakpoints
+breakpoints           list breakpoints
+watch CELL            stop when the cell is written
+watch CELL VALUE      stop when VALUE is written to the cell
+watch CELL zero       stop when the cell becomes zero
+unwatch [ID]          delete one or all watchpoints
+watchpoints           list watchpoints
+where             (w) show the current instruction
+tape [RADIUS]     (t) show the cells around the pointer
+loops             (l) show the loop nesting stack
+input TEXT            feed input, supports \\n \\t \\0 \\\\ and \\xHH
+restart               run from the beginning
+quit              (q)";
+
+/// 解析 LINE[:COLUMN] 或 #INDEX
+pub fn parse_breakpoint(arg: &str) -> Option<Breakpoint> {
+    if let Some(index) = arg.strip_prefix('#') {
+        return index.parse().ok().map(Breakpoint::Instruction);
+    }
+    match arg.split_once(':') {
+        Some((line, column)) => Some(Breakpoint::Source {
+            line: line.parse().ok()?,
+            column: Some(column.parse().ok()?),
+        }),
+        None => Some(Breakpoint::Source { line: arg.parse().ok()?, column: None }),
+    }
+}
+
+/// 解析 CELL [VALUE|zero]
+pub fn parse_watchpoint(arg: &str) -> Option<Watchpoint> {
+    let mut words = arg.split_whitespace();
+    let cell = words.next()?.parse().ok()?;
+    let watch = match words.next() {
+        None => Watch::Write,
+        Some("zero") => Watch::Zero,
+        Some(value) => Watch::Value(value.parse().ok()?),
+    };
+    if words.next().is_some() {
+        return None;
+    }
+    Some(Watchpoint { cell, watch })
+}
+
+/// 解析命令的数字参数，出错时返回命令的用法
+fn parse_number<T: std::str::FromStr>(arg: &str, usage: &str) -> Result<T, Box<dyn std::error::Error>> {
+    arg.parse().map_err(|_| format!("Usage: {}", usage).into())
+}
+
+/// 处理输入中的转义字符，\x 后面必须是两位十六进制数
+pub fn unescape(s: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut data = Vec::new();
+    let mut bytes = s.bytes();
+    while let Some(c) = bytes.next() {
+        if c != b'\\' {
+            data.push(c);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'n') => data.push(b'\n'),
+            Some(b't') => data.push(b'\t'),
+            Some(b'0') => data.push(0),
+            Some(b'x') => {
+                let hex: Vec<u8> = bytes.by_ref().take(2).collect();
+                let hex = String::from_utf8_lossy(&hex).into_owned();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(c) if hex.len() == 2 && hex.bytes().all(|c| c.is_ascii_hexdigit()) => data.push(c),
+                    _ => return Err(format!("Invalid escape \\x{}", hex).into()),
+                }
+            }
+            Some(c) => data.push(c),
+            None => data.push(b'\\'),
+        }
+    }
+    Ok(data)
+}
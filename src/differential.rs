@@ -0,0 +1,209 @@
+use super::engine::Engine;
+use super::interpreter;
+use super::interpreter_it;
+use super::limit::{Aborted, Limits};
+
+use std::io::prelude::*;
+use std::sync::{Arc, Mutex};
+
+const CONTEXT: usize = 8; // 报告分歧时显示前后多少个字节或单元
+
+/// 一个引擎执行程序的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub engine: Engine,
+    pub output: Vec<u8>,
+    pub tape: Vec<u8>, // 去掉了末尾的零，各引擎纸带的初始长度和扩展方式不同
+    pub pointer: usize,
+    pub error: Option<String>,
+    pub aborted: bool, // 被执行限制中止，各引擎计算步数的方式不同，中止时的状态不作比较
+}
+
+impl Outcome {
+    pub fn new(engine: Engine, output: Vec<u8>, tape: &[u8], pointer: usize, error: Option<String>) -> Self {
+        let len = tape.iter().rposition(|c| *c != 0).map_or(0, |i| i + 1);
+        Self { engine, output, tape: tape[..len].to_vec(), pointer, error, aborted: false }
+    }
+
+    fn cell(&self, i: usize) -> u8 {
+        self.tape.get(i).copied().unwrap_or(0)
+    }
+}
+
+/// 两个引擎结果的第一处不同
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difference {
+    Error,         // 一个执行成功，另一个出错
+    Output(usize), // 输出的第几个字节
+    Pointer,
+    Tape(usize), // 纸带的第几个单元
+}
+
+/// 一个引擎和基准引擎的分歧
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub expected: Outcome, // 基准引擎的结果
+    pub actual: Outcome,
+    pub difference: Difference,
+}
+
+impl Divergence {
+    /// 以第一个结果为基准，找到第一个和它不同的结果
+    pub fn find(outcomes: &[Outcome]) -> Option<Divergence> {
+        let (expected, rest) = outcomes.split_first()?;
+        rest.iter().find_map(|actual| {
+            let difference = if expected.error.is_some() != actual.error.is_some() || expected.aborted != actual.aborted {
+                Difference::Error
+            } else if expected.aborted {
+                return None;
+            } else if let Some(i) = first_difference(&expected.output, &actual.output) {
+                Difference::Output(i)
+            } else if expected.pointer != actual.pointer {
+                Difference::Pointer
+            } else if let Some(i) = (0..expected.tape.len().max(actual.tape.len())).find(|&i| expected.cell(i) != actual.cell(i)) {
+                Difference::Tape(i)
+            } else {
+                return None;
+            };
+            Some(Divergence { expected: expected.clone(), actual: actual.clone(), difference })
+        })
+    }
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (expected, actual) = (&self.expected, &self.actual);
+        let width = expected.engine.to_string().len().max(actual.engine.to_string().len());
+        match self.difference {
+            Difference::Error => {
+                writeln!(f, "{} and {} disagree on whether the program fails", expected.engine, actual.engine)?;
+                for outcome in [expected, actual] {
+                    let result = outcome.error.as_deref().unwrap_or("ok");
+                    writeln!(f, "  {:<width$}  {}", outcome.engine, result)?;
+                }
+            }
+            Difference::Output(i) => {
+                writeln!(f, "{} and {} disagree at output byte {}", expected.engine, actual.engine, i)?;
+                let from = i.saturating_sub(CONTEXT);
+                for outcome in [expected, actual] {
+                    let to = (i + CONTEXT + 1).min(outcome.output.len());
+                    let shown = outcome.output.get(from..to).unwrap_or_default();
+                    writeln!(f, "  {:<width$}  {} bytes, from {}: \"{}\"", outcome.engine, outcome.output.len(), from, shown.escape_ascii())?;
+                }
+            }
+            Difference::Pointer => {
+                writeln!(f, "{} and {} disagree on the final pointer", expected.engine, actual.engine)?;
+                for outcome in [expected, actual] {
+                    writeln!(f, "  {:<width$}  {}", outcome.engine, outcome.pointer)?;
+                }
+            }
+            Difference::Tape(i) => {
+                writeln!(f, "{} and {} disagree at tape cell {} (pointer {})", expected.engine, actual.engine, i, expected.pointer)?;
+                let from = i.saturating_sub(CONTEXT);
+                for outcome in [expected, actual] {
+                    let cells: Vec<String> = (from..=i + CONTEXT).map(|j| outcome.cell(j).to_string()).collect();
+                    writeln!(f, "  {:<width$}  from {}: [{}]", outcome.engine, from, cells.join(", "))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    match a.iter().zip(b).position(|(x, y)| x != y) {
+        Some(i) => Some(i),
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        None => None,
+    }
+}
+
+/// 输出写到共享的缓冲区，克隆后交给引擎，执行结束后取回
+#[derive(Clone, Default)]
+pub struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Capture {
+    /// 取回写入的内容，缓冲区清空
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 每个引擎使用的执行选项，输入输出由差分测试提供
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub limits: Limits,
+    pub debug: Option<usize>, // 启用 # 调试指令时，输出指针左右多少个单元
+}
+
+/// 按 options 设置引擎，用同样的输入执行程序，记录输出、纸带、指针和错误
+macro_rules! execute_engine {
+    ($interpreter:expr, $engine:expr, $data:expr, $input:expr, $options:expr) => {{
+        let options = $options;
+        let capture = Capture::default();
+        let mut interpreter = $interpreter
+            .with_limits(options.limits.clone())
+            .with_input(std::io::Cursor::new($input.to_vec()))
+            .with_output(capture.clone());
+        if let Some(radius) = options.debug {
+            interpreter = interpreter.with_debug(radius);
+        }
+        let result = interpreter.run($data.to_vec());
+        let aborted = matches!(&result, Err(e) if e.is::<Aborted>());
+        let error = result.err().map(|e| e.to_string());
+        Outcome { aborted, ..Outcome::new($engine, capture.take(), interpreter.tape(), interpreter.pointer(), error) }
+    }};
+}
+
+/// 在一个引擎上执行程序
+pub fn execute(engine: Engine, data: &[u8], input: &[u8], options: &Options) -> Result<Outcome, Box<dyn std::error::Error>> {
+    match engine {
+        Engine::Naive => Ok(execute_engine!(interpreter::Interpreter::default(), engine, data, input, options)),
+        Engine::It => Ok(execute_engine!(interpreter_it::Interpreter::default(), engine, data, input, options)),
+        Engine::Tiered => execute_tiered(data, input, options),
+        Engine::Jit => execute_jit(data, input, options),
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn execute_tiered(data: &[u8], input: &[u8], options: &Options) -> Result<Outcome, Box<dyn std::error::Error>> {
+    Ok(execute_engine!(super::tiered::Interpreter::default(), Engine::Tiered, data, input, options))
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn execute_tiered(_: &[u8], _: &[u8], _: &Options) -> Result<Outcome, Box<dyn std::error::Error>> {
+    Err("The tiered engine is not supported on this platform".into())
+}
+
+#[cfg(any(all(target_os = "linux", target_arch = "x86_64"), target_arch = "aarch64"))]
+fn execute_jit(data: &[u8], input: &[u8], options: &Options) -> Result<Outcome, Box<dyn std::error::Error>> {
+    Ok(execute_engine!(super::Interpreter::default(), Engine::Jit, data, input, options))
+}
+
+#[cfg(not(any(all(target_os = "linux", target_arch = "x86_64"), target_arch = "aarch64")))]
+fn execute_jit(_: &[u8], _: &[u8], _: &Options) -> Result<Outcome, Box<dyn std::error::Error>> {
+    Err("The JIT is not supported on this platform".into())
+}
+
+/// 在当前平台支持的所有引擎上执行程序，结果不一致时返回第一处分歧。
+/// 各引擎计算步数的方式不同，被执行限制中止时只比较是否中止；程序不能越过 JIT 固定长度的纸带
+pub fn check(data: &[u8], input: &[u8], options: &Options) -> Result<Vec<Outcome>, Box<dyn std::error::Error>> {
+    let outcomes = Engine::all().into_iter().map(|engine| execute(engine, data, input, options)).collect::<Result<Vec<_>, _>>()?;
+    match Divergence::find(&outcomes) {
+        Some(divergence) => Err(divergence.into()),
+        None => Ok(outcomes),
+    }
+}
@@ -0,0 +1,129 @@
+use super::interpreter;
+use super::interpreter_it;
+use super::limit::Limits;
+
+use std::io::prelude::*;
+use std::time::{Duration, Instant};
+
+/// 执行引擎
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    Naive, // interpreter，逐条解释源码
+    It,    // interpreter_it，解释中间表示
+    Tiered, // 解释中间表示，把热点循环编译成机器码，只支持 x86_64 Linux
+    Jit,   // 编译成机器码，只支持 x86_64 Linux 和 aarch64
+}
+
+/// 按 options 设置引擎并执行程序，返回所用的时间
+macro_rules! run_engine {
+    ($interpreter:expr, $data:expr, $options:expr) => {{
+        let options = $options;
+        let mut interpreter = $interpreter.with_limits(options.limits);
+        if let Some(radius) = options.debug {
+            interpreter = interpreter.with_debug(radius);
+        }
+        if let Some(input) = options.input {
+            interpreter = interpreter.with_input(input);
+        }
+        if let Some(output) = options.output {
+            interpreter = interpreter.with_output(output);
+        }
+        let start = Instant::now();
+        interpreter.run($data)?;
+        let total = start.elapsed();
+        let compile = interpreter.compile_time();
+        Ok(Timing { compile, execute: total.saturating_sub(compile) })
+    }};
+}
+
+impl Engine {
+    /// 当前平台支持的所有引擎，按优化程度从低到高排列
+    pub fn all() -> Vec<Engine> {
+        [Engine::Naive, Engine::It, Engine::Tiered, Engine::Jit].into_iter().filter(|engine| engine.is_supported()).collect()
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            Engine::Naive | Engine::It => true,
+            Engine::Tiered => cfg!(all(target_os = "linux", target_arch = "x86_64")),
+            Engine::Jit => cfg!(any(all(target_os = "linux", target_arch = "x86_64"), target_arch = "aarch64")),
+        }
+    }
+
+    /// 执行程序，没有指定输入输出时使用 stdin 和 stdout
+    pub fn run(self, data: Vec<u8>, options: Options) -> Result<(), Box<dyn std::error::Error>> {
+        self.run_timed(data, options).map(|_| ())
+    }
+
+    /// 执行程序，返回解析编译和执行分别所用的时间
+    pub fn run_timed(self, data: Vec<u8>, options: Options) -> Result<Timing, Box<dyn std::error::Error>> {
+        match self {
+            Engine::Naive => run_engine!(interpreter::Interpreter::default(), data, options),
+            Engine::It => run_engine!(interpreter_it::Interpreter::default(), data, options),
+            Engine::Tiered => run_tiered(data, options),
+            Engine::Jit => run_jit(data, options),
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+fn run_tiered(data: Vec<u8>, options: Options) -> Result<Timing, Box<dyn std::error::Error>> {
+    run_engine!(super::tiered::Interpreter::default(), data, options)
+}
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+fn run_tiered(_: Vec<u8>, _: Options) -> Result<Timing, Box<dyn std::error::Error>> {
+    Err("The tiered engine is not supported on this platform".into())
+}
+
+#[cfg(any(all(target_os = "linux", target_arch = "x86_64"), target_arch = "aarch64"))]
+fn run_jit(data: Vec<u8>, options: Options) -> Result<Timing, Box<dyn std::error::Error>> {
+    run_engine!(super::Interpreter::default(), data, options)
+}
+
+#[cfg(not(any(all(target_os = "linux", target_arch = "x86_64"), target_arch = "aarch64")))]
+fn run_jit(_: Vec<u8>, _: Options) -> Result<Timing, Box<dyn std::error::Error>> {
+    Err("The JIT is not supported on this platform".into())
+}
+
+impl std::str::FromStr for Engine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "naive" => Ok(Engine::Naive),
+            "it" => Ok(Engine::It),
+            "tiered" => Ok(Engine::Tiered),
+            "jit" => Ok(Engine::Jit),
+            _ => Err(format!("Unknown engine {}, expected naive, it, tiered or jit", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Engine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Engine::Naive => "naive",
+            Engine::It => "it",
+            Engine::Tiered => "tiered",
+            Engine::Jit => "jit",
+        };
+        f.pad(name)
+    }
+}
+
+/// 所有引擎共用的执行选项
+#[derive(Default)]
+pub struct Options {
+    pub limits: Limits,
+    pub debug: Option<usize>, // 启用 # 调试指令时，输出指针左右多少个单元
+    pub input: Option<Box<dyn Read + Send>>,   // , 的输入，默认为 stdin
+    pub output: Option<Box<dyn Write + Send>>, // . 的输出，默认为 stdout
+}
+
+/// 一次执行所用的时间
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timing {
+    pub compile: Duration, // 解析和编译，tiered 包括执行中编译热点循环的时间
+    pub execute: Duration, // 其余的执行时间
+}
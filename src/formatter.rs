@@ -0,0 +1,212 @@
+use super::debugger::SourceMap;
+use super::opcode::{self, Token};
+
+pub const DEFAULT_WIDTH: usize = 80;
+pub const DEFAULT_INDENT: usize = 4;
+const MIN_WIDTH: usize = 16; // 缩进很深时，每行除缩进外至少放这么多字符
+
+/// 格式化前的语法树
+enum Node {
+    Run(u8, usize), // 连续 n 条相同的指令
+    Loop(Vec<Node>),
+    Comment(Vec<String>), // 去掉首尾空白的注释行，空字符串是空行
+}
+
+/// 按循环深度重新缩进源码。没有嵌套循环的循环放得下时写在一行，
+/// 连续相同的指令只在比一行长时才拆开，注释单独成行。
+/// 只改变指令之间的空白和注释，指令序列不变；对格式化的结果再次格式化，结果不变
+pub struct Formatter {
+    width: usize,   // 每行的最大宽度，包括缩进
+    indent: usize,  // 每层循环缩进的空格数
+    comments: bool, // 是否保留注释
+    debug: bool,    // 是否把 # 作为指令，和引擎的 with_debug 一致
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Self { width: DEFAULT_WIDTH, indent: DEFAULT_INDENT, comments: true, debug: false }
+    }
+}
+
+impl Formatter {
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// indent 不能超过 width，否则 format 返回错误
+    pub fn with_indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// comments 为 false 时去掉所有注释和空行
+    pub fn with_comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    /// debug 为 true 时 # 是指令，去掉注释时也会保留
+    pub fn with_debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    /// 括号不配对时返回错误，指出第一个没有配对的括号的行列号
+    pub fn format(&self, data: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+        if self.indent > self.width {
+            return Err(format!("Indent {} is larger than the width {}", self.indent, self.width).into());
+        }
+        let nodes = self.parse(data)?;
+        let mut layout = Layout { formatter: self, lines: Vec::new(), line: String::new() };
+        layout.nodes(&nodes, 0);
+        layout.flush(0);
+        while layout.lines.last().is_some_and(|line| line.is_empty()) {
+            layout.lines.pop();
+        }
+
+        let mut formatted = layout.lines.join("\n");
+        if !formatted.is_empty() {
+            formatted.push('\n');
+        }
+        Ok(formatted)
+    }
+
+    fn parse(&self, data: &[u8]) -> Result<Vec<Node>, Box<dyn std::error::Error>> {
+        let unmatched = |offset: usize| {
+            let (line, column) = SourceMap::new(data).position(offset);
+            format!("Unmatched {} at {}:{}", data[offset] as char, line, column)
+        };
+
+        let mut nodes = Vec::new();
+        let mut stack = Vec::new(); // 外层循环 [ 的偏移和外层已经解析的节点
+        for token in opcode::tokenize(data, self.debug) {
+            match token {
+                Token::Opcode(i, opcode::OPCODE_LSB) => stack.push((i, std::mem::take(&mut nodes))),
+                Token::Opcode(i, opcode::OPCODE_RSB) => {
+                    let (_, outer) = stack.pop().ok_or_else(|| unmatched(i))?;
+                    let body = std::mem::replace(&mut nodes, outer);
+                    nodes.push(Node::Loop(body));
+                }
+                Token::Opcode(_, u) => match nodes.last_mut() {
+                    Some(Node::Run(v, n)) if *v == u => *n += 1,
+                    _ => nodes.push(Node::Run(u, 1)),
+                },
+                Token::Comment(range) if self.comments => {
+                    let lines = comment_lines(&data[range]);
+                    if !lines.is_empty() {
+                        nodes.push(Node::Comment(lines));
+                    }
+                }
+                Token::Comment(_) => {}
+            }
+        }
+        if let Some((i, _)) = stack.first() {
+            return Err(unmatched(*i).into());
+        }
+        Ok(nodes)
+    }
+}
+
+/// 注释的每一行去掉首尾空白，连续的空行合并为一行。
+/// 第一段和最后一段是指令所在行的剩余部分，它们为空时不算空行
+fn comment_lines(comment: &[u8]) -> Vec<String> {
+    let text = String::from_utf8_lossy(comment);
+    let segments: Vec<&str> = text.split('\n').map(|segment| segment.trim()).collect();
+    let last = segments.len() - 1;
+
+    let mut lines: Vec<String> = Vec::new();
+    for (i, segment) in segments.into_iter().enumerate() {
+        if segment.is_empty() && (i == 0 || i == last || lines.last().is_some_and(|line| line.is_empty())) {
+            continue;
+        }
+        lines.push(segment.to_string());
+    }
+    lines
+}
+
+/// 没有嵌套循环和注释的循环写成一行
+fn inline(body: &[Node]) -> Option<String> {
+    let mut text = String::from("[");
+    for node in body {
+        match node {
+            Node::Run(u, n) => text.extend(std::iter::repeat_n(*u as char, *n)),
+            _ => return None,
+        }
+    }
+    text.push(']');
+    Some(text)
+}
+
+struct Layout<'a> {
+    formatter: &'a Formatter,
+    lines: Vec<String>,
+    line: String, // 正在填充的一行，不包括缩进
+}
+
+impl Layout<'_> {
+    /// depth 层循环中一行除缩进外的宽度
+    fn available(&self, depth: usize) -> usize {
+        self.formatter.width.saturating_sub(depth.saturating_mul(self.formatter.indent)).max(MIN_WIDTH)
+    }
+
+    fn indented(&self, depth: usize, text: &str) -> String {
+        format!("{}{}", " ".repeat(depth.saturating_mul(self.formatter.indent)), text)
+    }
+
+    fn flush(&mut self, depth: usize) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.lines.push(self.indented(depth, &line));
+        }
+    }
+
+    /// 追加一段不拆开的代码，当前行放不下时换行
+    fn push(&mut self, text: &str, depth: usize) {
+        if self.line.len() + text.len() > self.available(depth) {
+            self.flush(depth);
+        }
+        self.line.push_str(text);
+    }
+
+    fn nodes(&mut self, nodes: &[Node], depth: usize) {
+        let available = self.available(depth);
+        for node in nodes {
+            match node {
+                Node::Run(u, n) => {
+                    // 比一行长的连续指令按整行拆开
+                    let mut n = *n;
+                    if self.line.len() + n > available {
+                        self.flush(depth);
+                    }
+                    while n > available {
+                        self.line.extend(std::iter::repeat_n(*u as char, available));
+                        self.flush(depth);
+                        n -= available;
+                    }
+                    self.line.extend(std::iter::repeat_n(*u as char, n));
+                }
+                Node::Loop(body) => match inline(body) {
+                    Some(text) if text.len() <= available => self.push(&text, depth),
+                    _ => {
+                        self.flush(depth);
+                        self.lines.push(self.indented(depth, "["));
+                        self.nodes(body, depth + 1);
+                        self.flush(depth + 1);
+                        self.lines.push(self.indented(depth, "]"));
+                    }
+                },
+                Node::Comment(lines) => {
+                    self.flush(depth);
+                    for line in lines {
+                        if !line.is_empty() {
+                            self.lines.push(self.indented(depth, line));
+                        } else if self.lines.last().is_some_and(|line| !line.is_empty()) {
+                            self.lines.push(String::new());
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,12 @@
+use super::dump_tape;
+use super::io::Io;
+use super::limit::{Guard, Limits};
 use super::opcode;
+use super::trace::{Event, Tracer};
 
 use std::collections;
 use std::io::prelude::*;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, PartialEq, Eq)]
 enum Opcode {
@@ -13,6 +18,7 @@ enum Opcode {
     RSB,
     GETCHAR,
     PUTCHAR,
+    DEBUG,
 }
 
 impl From<u8> for Opcode {
@@ -26,6 +32,7 @@ impl From<u8> for Opcode {
             opcode::OPCODE_RSB => Opcode::RSB,
             opcode::OPCODE_GETCHAR => Opcode::GETCHAR,
             opcode::OPCODE_PUTCHAR => Opcode::PUTCHAR,
+            opcode::OPCODE_DEBUG => Opcode::DEBUG,
             _ => panic!("Unsupported opcode {}", u),
         }
     }
@@ -33,15 +40,16 @@ impl From<u8> for Opcode {
 
 struct Code {
     opcodes: Vec<Opcode>,
+    offsets: Vec<usize>, // 每条指令在源码中的偏移
     jump_table: collections::HashMap<usize, usize>,
 }
 
 impl Code {
-    fn from(data: Vec<u8>) -> Result<Self, Box<dyn std::error::Error>> {
-        let opcodes: Vec<Opcode> = data.iter()
-            .filter(|x| opcode::OPCODE_DICT.contains(x))
-            .map(|x| Opcode::from(*x))
-            .collect();
+    /// debug 为 true 时把 # 作为调试指令，否则作为注释
+    fn from(data: Vec<u8>, debug: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let (offsets, opcodes): (Vec<usize>, Vec<Opcode>) = opcode::opcodes(&data, debug)
+            .map(|(i, x)| (i, Opcode::from(x)))
+            .unzip();
 
         let mut jump_table = collections::HashMap::new();
         let mut stack = Vec::new();
@@ -57,8 +65,11 @@ impl Code {
                 jump_table.insert(i, j);
             }
         }
+        if !stack.is_empty() {
+            return Err("Unmatched [".into());
+        }
  
-        Ok(Code { opcodes, jump_table })
+        Ok(Code { opcodes, offsets, jump_table })
     }
 
 }
@@ -66,70 +77,155 @@ impl Code {
 /// 解释器
 pub struct Interpreter {
     stack: Vec<u8>, // 保存解释执行的结果
+    s_pointer: usize, // 指针，多次 run 之间保持不变
+    limits: Limits, // 执行限制
+    debug: Option<usize>, // 启用 # 调试指令时，输出指针左右多少个单元
+    tracer: Option<Tracer>, // 记录执行过程
+    io: Io,
+    compile_time: Duration, // 上一次 run 解析程序所用的时间
 }
 
 impl std::default::Default for Interpreter {
     fn default() -> Self {
-        Self { stack: vec![0; 1] }
+        Self { stack: vec![0; 1], s_pointer: 0, limits: Limits::default(), debug: None, tracer: None, io: Io::default(), compile_time: Duration::ZERO }
     }
 }
 
 impl Interpreter {
+    /// 设置最大指令数、超时时间和取消句柄，超出时返回 limit::Aborted
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// 启用 # 调试指令，执行到 # 时把指针和左右 radius 个单元输出到 stderr
+    pub fn with_debug(mut self, radius: usize) -> Self {
+        self.debug = Some(radius);
+        self
+    }
+
+    /// 把执行的每条指令记录到 tracer，run 结束时写到文件
+    pub fn with_trace(mut self, tracer: Tracer) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// 从 input 读取 , 的输入，默认为 stdin
+    pub fn with_input(mut self, input: impl Read + Send + 'static) -> Self {
+        self.io.input = Some(Box::new(input));
+        self
+    }
+
+    /// 把 . 的输出写到 output，默认为 stdout
+    pub fn with_output(mut self, output: impl Write + Send + 'static) -> Self {
+        self.io.output = Some(Box::new(output));
+        self
+    }
+
+    pub fn tape(&self) -> &[u8] {
+        &self.stack
+    }
+
+    pub fn pointer(&self) -> usize {
+        self.s_pointer
+    }
+
+    /// 上一次 run 解析程序所用的时间
+    pub fn compile_time(&self) -> Duration {
+        self.compile_time
+    }
+
+    /// 清空纸带，指针回到 0
+    pub fn reset(&mut self) {
+        self.stack = vec![0; 1];
+        self.s_pointer = 0;
+    }
+
+    /// 在上一次 run 结束时的纸带和指针上执行程序
     pub fn run(&mut self, data: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
-        let code = Code::from(data)?;
+        let start = Instant::now();
+        let code = Code::from(data, self.debug.is_some())?;
+        self.compile_time = start.elapsed();
         let opcodes = code.opcodes;
+        let offsets = code.offsets;
         let opcode_len = opcodes.len();
         let jump_table = code.jump_table;
         let mut pc = 0; // 程序计数器
-        let mut s_pointer = 0; // 指针
-        
+        let mut steps: u64 = 0; // 已执行的指令数
+        let mut guard = Guard::new(&self.limits);
+
         loop {
             if pc >= opcode_len {
                 break;
             }
 
+            if steps >= guard.budget {
+                guard.poll(steps, self.s_pointer)?;
+            }
+            steps += 1;
+
+            let at = pc;
+            let pointer = self.s_pointer;
+            let before = self.stack[self.s_pointer];
             let opcode = &opcodes[pc];
             match opcode {
                 Opcode::SHL => {
-                    if s_pointer > 0 {
-                        s_pointer -= 1;
-                    }
+                    self.s_pointer = self.s_pointer.saturating_sub(1);
                 }
                 Opcode::SHR => {
-                    s_pointer += 1;
-                    if s_pointer == self.stack.len() {
+                    self.s_pointer += 1;
+                    if self.s_pointer == self.stack.len() {
                         self.stack.push(0);
                     }
                 }
                 Opcode::ADD => {
-                    self.stack[s_pointer] = self.stack[s_pointer].overflowing_add(1).0;
+                    self.stack[self.s_pointer] = self.stack[self.s_pointer].overflowing_add(1).0;
                 }
                 Opcode::SUB => {
-                    self.stack[s_pointer] = self.stack[s_pointer].overflowing_sub(1).0;
+                    self.stack[self.s_pointer] = self.stack[self.s_pointer].overflowing_sub(1).0;
                 }
                 Opcode::LSB => {
-                    if self.stack[s_pointer] == 0 {
+                    if self.stack[self.s_pointer] == 0 {
                         pc = jump_table[&pc];
                     }
                 }
                 Opcode::RSB => {
-                    if self.stack[s_pointer] != 0 {
+                    if self.stack[self.s_pointer] != 0 {
+                        guard.check_cancel(steps, self.s_pointer)?;
                         pc = jump_table[&pc];
                     }
                 }
                 Opcode::GETCHAR => {
-                    let mut buf = [0; 1];
-                    std::io::stdin().read_exact(&mut buf)?;
-                    self.stack[s_pointer] = buf[0];
+                    self.stack[self.s_pointer] = self.io.read_byte()?;
                 }
                 Opcode::PUTCHAR => {
-                    std::io::stdout().write_all(&[self.stack[s_pointer]])?;
+                    self.io.write_all(&[self.stack[self.s_pointer]])?;
+                    guard.record(self.stack[self.s_pointer]);
+                }
+                Opcode::DEBUG => {
+                    self.io.flush()?;
+                    dump_tape(&self.stack, self.s_pointer, self.debug.unwrap_or_default());
                 }
             }
 
+            if let Some(tracer) = &mut self.tracer {
+                tracer.record(&Event {
+                    step: steps - 1,
+                    pc: at,
+                    offset: Some(offsets[at]),
+                    pointer,
+                    before,
+                    after: self.stack[pointer],
+                })?;
+            }
+
             pc += 1;
         }
 
+        if let Some(tracer) = &mut self.tracer {
+            tracer.flush()?;
+        }
+        self.io.flush()?;
         Ok(())
     }
 }
@@ -1,9 +1,18 @@
+use super::dump_tape;
+use super::io::Io;
+use super::limit::{Aborted, Guard, Limits};
 use super::opcode;
+use super::profile::Profile;
+use super::trace::{Event, Tracer};
 
+use std::collections::VecDeque;
 use std::io::prelude::*;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-#[derive(Debug, PartialEq, Eq)]
-enum ItOpcode {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItOpcode {
     SHL(u32), // SHL(10): 指针减 10
     SHR(u32), // SHR(10): 指针加 10
     ADD(u8),  // ADD(10): 指针指向的字节的值加 10
@@ -12,157 +21,776 @@ enum ItOpcode {
     RSB(u32), // RSB(0): 如果指针指向的单元值不为零，跳转到 0 处
     GETCHAR,
     PUTCHAR,
+    DEBUG,    // 启用调试扩展时的 #
 }
 
 struct Code {
     it_opcodes: Vec<ItOpcode>, // 中间表优化，去掉重复指令的 opcodes
+    spans: Vec<Range<usize>>,  // 每条指令在源码中对应的范围
 }
 
 impl Code {
-    fn from(data: Vec<u8>) -> Result<Self, Box<dyn std::error::Error>> {
-        let data: Vec<&u8> = data.iter()
-            .filter(|x| opcode::OPCODE_DICT.contains(x))
-            .collect();
+    /// debug 为 true 时把 # 作为调试指令，否则作为注释
+    fn from(data: Vec<u8>, debug: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let parsed = parse(&data, debug);
+        if parsed.unmatched.iter().any(|i| data[*i] == opcode::OPCODE_RSB) {
+            return Err("Pop from empty stack".into());
+        }
+        if !parsed.unmatched.is_empty() {
+            return Err("Unmatched [".into());
+        }
+        Ok(Code { it_opcodes: parsed.opcodes, spans: parsed.spans })
+    }
+}
 
-        let mut it_opcodes = Vec::new();
-        let mut stack = Vec::new(); // 存储 [ 指令下标
+/// 解析的结果，括号不配对时也会解析整个程序
+pub struct Parsed {
+    pub opcodes: Vec<ItOpcode>,
+    pub spans: Vec<Range<usize>>, // 每条指令在源码中对应的范围
+    pub unmatched: Vec<usize>,    // 没有配对的 [ 和 ] 在源码中的偏移，没有配对的 ] 不生成指令
+}
 
-        for u in data {
-            match *u {
-                opcode::OPCODE_SHL => match it_opcodes.last_mut() {
-                    Some(ItOpcode::SHL(v)) => {
-                        *v += 1;
-                    }
-                    _ => {
-                        it_opcodes.push(ItOpcode::SHL(1));
-                    }
+/// 把源码解析为中间表示，debug 为 true 时把 # 作为调试指令，否则作为注释
+pub fn parse(data: &[u8], debug: bool) -> Parsed {
+    let mut it_opcodes = Vec::new();
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    let mut stack = Vec::new(); // 存储 [ 指令下标和源码偏移
+    let mut unmatched = Vec::new();
+
+    for (i, u) in opcode::opcodes(data, debug) {
+        let len = it_opcodes.len();
+        match u {
+            opcode::OPCODE_SHL => match it_opcodes.last_mut() {
+                Some(ItOpcode::SHL(v)) => {
+                    *v += 1;
                 }
-                opcode::OPCODE_SHR => match it_opcodes.last_mut() {
-                    Some(ItOpcode::SHR(v)) => {
-                        *v += 1;
-                    }
-                    _ => {
-                        it_opcodes.push(ItOpcode::SHR(1));
-                    }
+                _ => {
+                    it_opcodes.push(ItOpcode::SHL(1));
                 }
-                opcode::OPCODE_ADD => match it_opcodes.last_mut() {
-                    Some(ItOpcode::ADD(x)) => {
-                        let v = x.overflowing_add(1).0;
-                        *x = v;
-                    }
-                    _ => {
-                        it_opcodes.push(ItOpcode::ADD(1));
-                    }
+            }
+            opcode::OPCODE_SHR => match it_opcodes.last_mut() {
+                Some(ItOpcode::SHR(v)) => {
+                    *v += 1;
                 }
-                opcode::OPCODE_SUB => match it_opcodes.last_mut() {
-                    Some(ItOpcode::SUB(x)) => {
-                        let v = x.overflowing_add(1).0;
-                        *x = v;
-                    }
-                    _ => {
-                        it_opcodes.push(ItOpcode::SUB(1));
-                    }
+                _ => {
+                    it_opcodes.push(ItOpcode::SHR(1));
                 }
-                opcode::OPCODE_LSB => {
-                    it_opcodes.push(ItOpcode::LSB(0)); // 先存入 LSB(0)
-                    stack.push(it_opcodes.len() - 1); // 记录 [ 所在的位置
+            }
+            opcode::OPCODE_ADD => match it_opcodes.last_mut() {
+                Some(ItOpcode::ADD(x)) => {
+                    let v = x.overflowing_add(1).0;
+                    *x = v;
                 }
-                opcode::OPCODE_RSB => {
-                    let i = stack.pop().ok_or("Pop from empty stack")?;
-                    it_opcodes.push(ItOpcode::RSB(i as u32));
-                    let len = it_opcodes.len() - 1; // 最近的 ] 所在的位置
-                    if let ItOpcode::LSB(v) = &mut it_opcodes[i] {
-                        *v = (len - 1) as u32; // 修改 [ 配对的 ] 所在的位置
-                    }
+                _ => {
+                    it_opcodes.push(ItOpcode::ADD(1));
                 }
-                opcode::OPCODE_GETCHAR => {
-                    it_opcodes.push(ItOpcode::GETCHAR);
+            }
+            opcode::OPCODE_SUB => match it_opcodes.last_mut() {
+                Some(ItOpcode::SUB(x)) => {
+                    let v = x.overflowing_add(1).0;
+                    *x = v;
                 }
-                opcode::OPCODE_PUTCHAR => {
-                    it_opcodes.push(ItOpcode::PUTCHAR);
+                _ => {
+                    it_opcodes.push(ItOpcode::SUB(1));
                 }
-                _ => panic!("Unsupported opcode {}", u),
             }
+            opcode::OPCODE_LSB => {
+                it_opcodes.push(ItOpcode::LSB(0)); // 先存入 LSB(0)
+                stack.push((it_opcodes.len() - 1, i)); // 记录 [ 所在的位置
+            }
+            opcode::OPCODE_RSB => {
+                let Some((j, _)) = stack.pop() else {
+                    unmatched.push(i);
+                    continue;
+                };
+                it_opcodes.push(ItOpcode::RSB(j as u32));
+                let len = it_opcodes.len() - 1; // 最近的 ] 所在的位置
+                if let ItOpcode::LSB(v) = &mut it_opcodes[j] {
+                    *v = (len - 1) as u32; // 修改 [ 配对的 ] 所在的位置
+                }
+            }
+            opcode::OPCODE_GETCHAR => {
+                it_opcodes.push(ItOpcode::GETCHAR);
+            }
+            opcode::OPCODE_PUTCHAR => {
+                it_opcodes.push(ItOpcode::PUTCHAR);
+            }
+            opcode::OPCODE_DEBUG => {
+                it_opcodes.push(ItOpcode::DEBUG);
+            }
+            _ => panic!("Unsupported opcode {}", u),
         }
 
-        Ok(Code { it_opcodes })
+        match spans.last_mut() {
+            // 合并到了上一条指令
+            Some(span) if it_opcodes.len() == len => span.end = i + 1,
+            _ => spans.push(i..i + 1),
+        }
     }
+
+    unmatched.extend(stack.into_iter().map(|(_, i)| i));
+    unmatched.sort_unstable();
+    Parsed { opcodes: it_opcodes, spans, unmatched }
+}
+
+/// 执行状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Running,    // 还有指令没有执行
+    NeedsInput, // 执行到 , 时没有输入，调用 feed 之后可以继续执行
+    Debug,      // 执行了 # 调试指令，可以直接继续执行
+    Halted,     // 执行结束
 }
 
-/// 解释器
+/// 调试器反向执行时保存的执行状态，不包括输出
+#[derive(Clone)]
+pub(crate) struct Frame {
+    pc: usize,
+    s_pointer: usize,
+    steps: u64,
+    stack: Vec<u8>,
+    input: VecDeque<u8>,
+}
+
+const RUN_SLICE: u64 = 1 << 16; // run 每执行这么多步检查一次快照
+
+const SNAPSHOT_MAGIC: &[u8; 6] = b"BFSNAP";
+const SNAPSHOT_VERSION: u16 = 2;
+
+#[cfg(feature = "tokio")]
+const ASYNC_SLICE: u64 = 1 << 14; // run_async 每执行这么多步让出一次
+
+/// 解释器。
+/// 除了 run 以外，也可以用 load 加载代码后通过 step、run_for、run_until_input 分段执行，
+/// 输入通过 feed 提供，输出通过 take_output 取走，暂停时状态保持不变
 pub struct Interpreter {
     stack: Vec<u8>, // 保存解释执行的结果
+    limits: Limits, // 执行限制
+    debug: Option<usize>, // 启用 # 调试指令时，输出指针左右多少个单元
+    guard: Guard,
+    it_opcodes: Vec<ItOpcode>,
+    spans: Vec<Range<usize>>, // 每条指令在源码中对应的范围，从快照恢复时为空
+    pc: usize,             // 程序计数器
+    s_pointer: usize,      // 指针
+    steps: u64,            // 已执行的指令数
+    input: VecDeque<u8>,   // 还没有读取的输入
+    output: Vec<u8>,       // 还没有取走的输出
+    output_offset: u64,    // 已经取走的输出字节数
+    checkpoint: Option<(PathBuf, u64)>, // resume 每执行多少步保存一次快照
+    tracer: Option<Tracer>, // 记录执行过程
+    profiling: bool,        // 是否统计每条指令的执行次数
+    counts: Vec<u64>,       // 每条指令的执行次数
+    io: Io,                 // run 和 resume 的输入输出
+    compile_time: Duration, // 上一次 load 解析程序所用的时间
 }
 
 impl std::default::Default for Interpreter {
     fn default() -> Self {
-        Self { stack: vec![0; 1] }
+        let limits = Limits::default();
+        Self {
+            stack: vec![0; 1],
+            guard: Guard::new(&limits),
+            limits,
+            debug: None,
+            it_opcodes: Vec::new(),
+            spans: Vec::new(),
+            pc: 0,
+            s_pointer: 0,
+            steps: 0,
+            input: VecDeque::new(),
+            output: Vec::new(),
+            output_offset: 0,
+            checkpoint: None,
+            tracer: None,
+            profiling: false,
+            counts: Vec::new(),
+            io: Io::default(),
+            compile_time: Duration::ZERO,
+        }
     }
 }
 
 impl Interpreter {
+    /// 设置最大指令数、超时时间和取消句柄，超出时返回 limit::Aborted。
+    /// 指令数按中间表计算，ADD(10) 算一条
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.guard = Guard::new(&limits);
+        self.limits = limits;
+        self
+    }
+
+    /// 启用 # 调试指令。run 执行到 # 时把指针和左右 radius 个单元输出到 stderr，
+    /// 分段执行时返回 State::Debug
+    pub fn with_debug(mut self, radius: usize) -> Self {
+        self.debug = Some(radius);
+        self
+    }
+
+    /// 加载代码，从第一条指令开始执行。纸带、指针和没有读取的输入保持不变，
+    /// 可以在上一段代码的执行结果上继续执行
+    pub fn load(&mut self, data: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+        let start = Instant::now();
+        let code = Code::from(data, self.debug.is_some())?;
+        self.compile_time = start.elapsed();
+        self.it_opcodes = code.it_opcodes;
+        self.spans = code.spans;
+        self.counts = if self.profiling { vec![0; self.it_opcodes.len()] } else { Vec::new() };
+        self.pc = 0;
+        self.steps = 0;
+        self.output_offset = 0;
+        self.guard = Guard::new(&self.limits);
+
+        Ok(())
+    }
+
+    /// 清空纸带、指针、输入和输出，已加载的代码回到第一条指令
+    pub fn reset(&mut self) {
+        self.stack = vec![0; 1];
+        self.s_pointer = 0;
+        self.pc = 0;
+        self.steps = 0;
+        self.input.clear();
+        self.output.clear();
+        self.output_offset = 0;
+        self.counts.iter_mut().for_each(|count| *count = 0);
+        self.guard = Guard::new(&self.limits);
+    }
+
+    /// 提供输入
+    pub fn feed(&mut self, data: &[u8]) {
+        self.input.extend(data);
+    }
+
+    /// 还没有读取的输入
+    pub fn input(&self) -> &VecDeque<u8> {
+        &self.input
+    }
+
+    /// 取走已经产生的输出
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.output_offset += self.output.len() as u64;
+        std::mem::take(&mut self.output)
+    }
+
+    /// 已经取走的输出字节数
+    pub fn output_offset(&self) -> u64 {
+        self.output_offset
+    }
+
+    /// 丢弃还没有取走的输出中 len 之后的部分
+    pub(crate) fn truncate_output(&mut self, len: usize) {
+        self.output.truncate(len);
+    }
+
+    /// 还没有取走的输出
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    pub(crate) fn frame(&self) -> Frame {
+        Frame {
+            pc: self.pc,
+            s_pointer: self.s_pointer,
+            steps: self.steps,
+            stack: self.stack.clone(),
+            input: self.input.clone(),
+        }
+    }
+
+    /// 回到保存的执行状态，输出保持不变
+    pub(crate) fn set_frame(&mut self, frame: &Frame) {
+        self.pc = frame.pc;
+        self.s_pointer = frame.s_pointer;
+        self.steps = frame.steps;
+        self.stack.clone_from(&frame.stack);
+        self.input.clone_from(&frame.input);
+    }
+
+    /// 撤销最后执行的一条指令。
+    /// pc 和 pointer 是执行前的值，old 是执行前指针指向的单元的值，input 是指令读取的输入
+    pub(crate) fn rewind(&mut self, pc: usize, pointer: usize, old: u8, input: Option<u8>) {
+        self.pc = pc;
+        self.s_pointer = pointer;
+        self.stack[pointer] = old;
+        self.steps -= 1;
+        if let Some(c) = input {
+            self.input.push_front(c);
+        }
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn pointer(&self) -> usize {
+        self.s_pointer
+    }
+
+    /// 上一次 load 解析程序所用的时间
+    pub fn compile_time(&self) -> Duration {
+        self.compile_time
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn tape(&self) -> &[u8] {
+        &self.stack
+    }
+
+    /// 加载的中间表指令
+    pub fn opcodes(&self) -> &[ItOpcode] {
+        &self.it_opcodes
+    }
+
+    /// 第 pc 条指令在源码中对应的范围
+    pub fn span(&self, pc: usize) -> Option<Range<usize>> {
+        self.spans.get(pc).cloned()
+    }
+
+    /// 执行一条指令
+    pub fn step(&mut self) -> Result<State, Box<dyn std::error::Error>> {
+        self.execute_slice(1, false).map_err(|e| e as _)
+    }
+
+    /// 最多执行 steps 条指令
+    pub fn run_for(&mut self, steps: u64) -> Result<State, Box<dyn std::error::Error>> {
+        self.execute_slice(steps, false).map_err(|e| e as _)
+    }
+
+    /// 执行到需要输入或者结束
+    pub fn run_until_input(&mut self) -> Result<State, Box<dyn std::error::Error>> {
+        loop {
+            match self.execute_slice(u64::MAX, false).map_err(|e| e as Box<dyn std::error::Error>)? {
+                State::Running => {}
+                state => return Ok(state),
+            }
+        }
+    }
+
+    /// resume 执行时每隔 every 步把快照保存到 path
+    pub fn with_checkpoint(mut self, path: impl Into<PathBuf>, every: u64) -> Self {
+        self.checkpoint = Some((path.into(), every));
+        self
+    }
+
+    /// 把执行的每条指令记录到 tracer，此时逐条执行，速度较慢
+    pub fn with_trace(mut self, tracer: Tracer) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// 统计每条指令的执行次数，执行后通过 profile 取得
+    pub fn with_profile(mut self) -> Self {
+        self.profiling = true;
+        self
+    }
+
+    /// 加载以来每条指令的执行次数，没有调用 with_profile 时返回 None
+    pub fn profile(&self) -> Option<Profile> {
+        if !self.profiling {
+            return None;
+        }
+        Some(Profile::new(self.it_opcodes.clone(), self.spans.clone(), self.counts.clone()))
+    }
+
+    /// 把缓冲的执行记录写到文件，resume 和 run_async 结束时会自动调用
+    pub fn flush_trace(&mut self) -> std::io::Result<()> {
+        match &mut self.tracer {
+            Some(tracer) => tracer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    /// run 和 resume 从 input 读取 , 的输入，默认为 stdin。feed 提供的输入先被读取
+    pub fn with_input(mut self, input: impl Read + Send + 'static) -> Self {
+        self.io.input = Some(Box::new(input));
+        self
+    }
+
+    /// run 和 resume 把输出写到 output，默认为 stdout
+    pub fn with_output(mut self, output: impl Write + Send + 'static) -> Self {
+        self.io.output = Some(Box::new(output));
+        self
+    }
+
+    /// 在上一次 run 结束时的纸带和指针上执行程序
     pub fn run(&mut self, data: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
-        let code = Code::from(data)?;
-        let it_opcodes = code.it_opcodes;
-        let it_opcode_len = it_opcodes.len();
+        self.load(data)?;
+        self.resume()
+    }
+
+    /// 从当前状态继续执行到结束，输入输出默认使用 stdin/stdout
+    pub fn resume(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut next_checkpoint = self.checkpoint.as_ref().map(|(_, every)| self.steps + every);
 
-        let mut pc = 0; // 程序计数器
-        let mut s_pointer = 0; // 指针
-        
         loop {
+            let state = self.execute_slice(RUN_SLICE, true).map_err(|e| e as Box<dyn std::error::Error>);
+            let output = self.take_output();
+            self.io.write_all(&output)?;
+
+            if let (Some(steps), Some((path, every))) = (next_checkpoint, &self.checkpoint) {
+                if self.steps >= steps {
+                    self.io.flush()?;
+                    self.save_to(path)?;
+                    next_checkpoint = Some(self.steps + every);
+                }
+            }
+
+            match state? {
+                State::Running => {}
+                State::NeedsInput => {
+                    self.io.flush()?;
+                    let c = self.io.read_byte()?;
+                    self.feed(&[c]);
+                }
+                State::Debug => {
+                    self.io.flush()?;
+                    dump_tape(&self.stack, self.s_pointer, self.debug.unwrap_or_default());
+                }
+                State::Halted => break,
+            }
+        }
+
+        self.io.flush()?;
+        self.flush_trace()?;
+        Ok(())
+    }
+
+    /// 保存完整的执行状态：代码和源码位置、纸带、指针、程序计数器、未读取的输入和输出位置
+    pub fn save<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        w.write_all(SNAPSHOT_MAGIC)?;
+        w.write_all(&SNAPSHOT_VERSION.to_le_bytes())?;
+        for v in [self.pc as u64, self.s_pointer as u64, self.steps, self.output_offset] {
+            w.write_all(&v.to_le_bytes())?;
+        }
+
+        w.write_all(&(self.it_opcodes.len() as u64).to_le_bytes())?;
+        for opcode in &self.it_opcodes {
+            let (tag, v): (u8, u32) = match *opcode {
+                ItOpcode::SHL(v) => (0, v),
+                ItOpcode::SHR(v) => (1, v),
+                ItOpcode::ADD(v) => (2, v as u32),
+                ItOpcode::SUB(v) => (3, v as u32),
+                ItOpcode::LSB(v) => (4, v),
+                ItOpcode::RSB(v) => (5, v),
+                ItOpcode::GETCHAR => (6, 0),
+                ItOpcode::PUTCHAR => (7, 0),
+                ItOpcode::DEBUG => (8, 0),
+            };
+            w.write_all(&[tag])?;
+            w.write_all(&v.to_le_bytes())?;
+        }
+
+        w.write_all(&(self.spans.len() as u64).to_le_bytes())?;
+        for span in &self.spans {
+            w.write_all(&(span.start as u64).to_le_bytes())?;
+            w.write_all(&(span.end as u64).to_le_bytes())?;
+        }
+
+        let input: Vec<u8> = self.input.iter().copied().collect();
+        for data in [&self.stack[..], &input[..], &self.output[..]] {
+            w.write_all(&(data.len() as u64).to_le_bytes())?;
+            w.write_all(data)?;
+        }
+
+        w.flush()
+    }
+
+    /// 写到临时文件后再改名，避免中途退出时留下不完整的快照
+    pub fn save_to(&self, path: &Path) -> std::io::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        self.save(std::io::BufWriter::new(std::fs::File::create(&tmp)?))?;
+        std::fs::rename(&tmp, path)
+    }
+
+    /// 恢复 save 保存的状态，之后可以继续执行
+    pub fn restore<R: Read>(&mut self, mut r: R) -> Result<(), Box<dyn std::error::Error>> {
+        let mut magic = [0; 6];
+        r.read_exact(&mut magic)?;
+        if &magic != SNAPSHOT_MAGIC {
+            return Err("Not a snapshot file".into());
+        }
+        let mut version = [0; 2];
+        r.read_exact(&mut version)?;
+        if u16::from_le_bytes(version) != SNAPSHOT_VERSION {
+            return Err("Unsupported snapshot version".into());
+        }
+
+        let pc = read_u64(&mut r)? as usize;
+        let s_pointer = read_u64(&mut r)? as usize;
+        let steps = read_u64(&mut r)?;
+        let output_offset = read_u64(&mut r)?;
+
+        let len = read_u64(&mut r)?;
+        let mut it_opcodes = Vec::new();
+        for _ in 0..len {
+            let mut buf = [0; 5];
+            r.read_exact(&mut buf)?;
+            let v = u32::from_le_bytes([buf[1], buf[2], buf[3], buf[4]]);
+            it_opcodes.push(match buf[0] {
+                0 => ItOpcode::SHL(v),
+                1 => ItOpcode::SHR(v),
+                2 => ItOpcode::ADD(v as u8),
+                3 => ItOpcode::SUB(v as u8),
+                4 => ItOpcode::LSB(v),
+                5 => ItOpcode::RSB(v),
+                6 => ItOpcode::GETCHAR,
+                7 => ItOpcode::PUTCHAR,
+                8 => ItOpcode::DEBUG,
+                tag => return Err(format!("Unsupported opcode tag {} in snapshot", tag).into()),
+            });
+        }
+
+        let len = read_u64(&mut r)?;
+        let mut spans = Vec::new();
+        for _ in 0..len {
+            let start = read_u64(&mut r)? as usize;
+            let end = read_u64(&mut r)? as usize;
+            spans.push(start..end);
+        }
+
+        let stack = read_bytes(&mut r)?;
+        let input = read_bytes(&mut r)?;
+        let output = read_bytes(&mut r)?;
+        if s_pointer >= stack.len() || pc > it_opcodes.len() || !jumps_match(&it_opcodes) {
+            return Err("Corrupted snapshot".into());
+        }
+        // 从 load 以外的途径得到的代码没有源码位置
+        if !spans.is_empty() && spans.len() != it_opcodes.len() {
+            return Err("Corrupted snapshot".into());
+        }
+
+        self.it_opcodes = it_opcodes;
+        self.spans = spans;
+        self.counts = if self.profiling { vec![0; self.it_opcodes.len()] } else { Vec::new() };
+        self.stack = stack;
+        self.pc = pc;
+        self.s_pointer = s_pointer;
+        self.steps = steps;
+        self.input = input.into();
+        self.output = output;
+        self.output_offset = output_offset;
+        self.guard = Guard::new(&self.limits);
+
+        Ok(())
+    }
+
+    /// 异步执行，从 reader 读取输入，输出写到 writer。
+    /// 需要输入、产生输出以及每执行 ASYNC_SLICE 步时让出，多个程序可以共享一个运行时。
+    /// 和 run 一样，执行到 # 时把纸带输出到 stderr
+    #[cfg(feature = "tokio")]
+    pub async fn run_async<R, W>(
+        &mut self,
+        data: Vec<u8>,
+        reader: &mut R,
+        writer: &mut W,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+    where
+        R: tokio::io::AsyncRead + Unpin,
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        self.load(data).map_err(|e| e.to_string())?;
+
+        loop {
+            let state = self.execute_slice(ASYNC_SLICE, true);
+            let output = self.take_output();
+            if !output.is_empty() {
+                writer.write_all(&output).await?;
+                writer.flush().await?;
+            }
+
+            match state? {
+                State::Running => tokio::task::yield_now().await,
+                State::Debug => {
+                    dump_tape(&self.stack, self.s_pointer, self.debug.unwrap_or_default());
+                    tokio::task::yield_now().await;
+                }
+                State::NeedsInput => {
+                    let mut buf = [0; 1];
+                    reader.read_exact(&mut buf).await.map_err(super::io::end_of_input)?;
+                    self.feed(&buf);
+                }
+                State::Halted => break,
+            }
+        }
+
+        self.flush_trace()?;
+        Ok(())
+    }
+
+    /// 最多执行 budget 条指令，需要记录执行过程时逐条执行。
+    /// pause_on_output 为 true 时每次输出后返回 State::Running，调用方可以马上把输出写出去
+    fn execute_slice(&mut self, budget: u64, pause_on_output: bool) -> Result<State, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(mut tracer) = self.tracer.take() else {
+            return Ok(self.execute(budget, pause_on_output)?);
+        };
+        let result = self.execute_traced(&mut tracer, budget, pause_on_output);
+        self.tracer = Some(tracer);
+        result
+    }
+
+    fn execute_traced(&mut self, tracer: &mut Tracer, budget: u64, pause_on_output: bool) -> Result<State, Box<dyn std::error::Error + Send + Sync>> {
+        let end = self.steps.saturating_add(budget);
+        let output_len = self.output.len();
+        let mut state = State::Running;
+
+        while self.steps < end && state == State::Running && !(pause_on_output && self.output.len() > output_len) {
+            let pc = self.pc;
+            let pointer = self.s_pointer;
+            let step = self.steps;
+            let before = self.stack[pointer];
+            state = self.execute(1, false)?;
+            if self.steps != step {
+                tracer.record(&Event {
+                    step,
+                    pc,
+                    offset: self.spans.get(pc).map(|span| span.start),
+                    pointer,
+                    before,
+                    after: self.stack[pointer],
+                })?;
+            }
+        }
+
+        Ok(state)
+    }
+
+    fn execute(&mut self, budget: u64, pause_on_output: bool) -> Result<State, Aborted> {
+        if self.profiling {
+            self.execute_opcodes::<true>(budget, pause_on_output)
+        } else {
+            self.execute_opcodes::<false>(budget, pause_on_output)
+        }
+    }
+
+    /// PROFILE 为 true 时统计每条指令的执行次数
+    fn execute_opcodes<const PROFILE: bool>(&mut self, budget: u64, pause_on_output: bool) -> Result<State, Aborted> {
+        let Self { stack, guard, it_opcodes, input, output, counts, .. } = self;
+        let it_opcode_len = it_opcodes.len();
+
+        let mut pc = self.pc;
+        let mut s_pointer = self.s_pointer;
+        let mut steps = self.steps;
+        let end = steps.saturating_add(budget);
+
+        let result = loop {
             if pc >= it_opcode_len {
-                break;
+                break Ok(State::Halted);
+            }
+            if steps >= end {
+                break Ok(State::Running);
+            }
+
+            if steps >= guard.budget {
+                if let Err(e) = guard.poll(steps, s_pointer) {
+                    break Err(e);
+                }
             }
 
+            let at = pc;
             let opcode = &it_opcodes[pc];
             match *opcode {
                 ItOpcode::SHL(v) => {
-                    for _ in 0..v as usize {
-                        if s_pointer <= 0 {
-                            break;
-                        }
-                        s_pointer -= 1;
-                    }
+                    s_pointer = s_pointer.saturating_sub(v as usize);
                 }
                 ItOpcode::SHR(v) => {
                     s_pointer += v as usize;
-                    if s_pointer >= self.stack.len() {
-                        let end = s_pointer - self.stack.len() + 1;
-                        for _ in 0..end {
-                            // 超过部分新增 0
-                            self.stack.push(0);
-                        }
+                    if s_pointer >= stack.len() {
+                        // 超过部分新增 0
+                        stack.resize(s_pointer + 1, 0);
                     }
                 }
                 ItOpcode::ADD(v) => {
-                    self.stack[s_pointer] = self.stack[s_pointer].overflowing_add(v).0;
+                    stack[s_pointer] = stack[s_pointer].overflowing_add(v).0;
                 }
                 ItOpcode::SUB(v) => {
-                    self.stack[s_pointer] = self.stack[s_pointer].overflowing_sub(v).0;
+                    stack[s_pointer] = stack[s_pointer].overflowing_sub(v).0;
                 }
                 ItOpcode::LSB(v) => {
-                    if self.stack[s_pointer] == 0 {
+                    if stack[s_pointer] == 0 {
                         pc = v as usize;
                     }
                 }
                 ItOpcode::RSB(v) => {
-                    if self.stack[s_pointer] != 0 {
+                    if stack[s_pointer] != 0 {
+                        if let Err(e) = guard.check_cancel(steps, s_pointer) {
+                            break Err(e);
+                        }
                         pc = v as usize;
                     }
                 }
-                ItOpcode::GETCHAR => {
-                    let mut buf = [0; 1];
-                    std::io::stdin().read_exact(&mut buf)?;
-                    self.stack[s_pointer] = buf[0];
-                }
+                ItOpcode::GETCHAR => match input.pop_front() {
+                    Some(c) => {
+                        stack[s_pointer] = c;
+                    }
+                    None => {
+                        break Ok(State::NeedsInput);
+                    }
+                },
                 ItOpcode::PUTCHAR => {
-                    std::io::stdout().write_all(&[self.stack[s_pointer]])?;
+                    output.push(stack[s_pointer]);
+                    guard.record(stack[s_pointer]);
+                    if pause_on_output {
+                        if PROFILE {
+                            counts[at] += 1;
+                        }
+                        pc += 1;
+                        steps += 1;
+                        break Ok(State::Running);
+                    }
+                }
+                ItOpcode::DEBUG => {
+                    if PROFILE {
+                        counts[at] += 1;
+                    }
+                    pc += 1;
+                    steps += 1;
+                    break Ok(State::Debug);
                 }
             }
 
+            if PROFILE {
+                counts[at] += 1;
+            }
             pc += 1;
-        }
+            steps += 1;
+        };
 
-        Ok(())
+        self.pc = pc;
+        self.s_pointer = s_pointer;
+        self.steps = steps;
+
+        result
+    }
+}
+
+fn read_u64<R: Read>(r: &mut R) -> std::io::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// 每个 [ 和 ] 都跳到和自己配对的位置
+fn jumps_match(it_opcodes: &[ItOpcode]) -> bool {
+    it_opcodes.iter().enumerate().all(|(i, opcode)| match *opcode {
+        // [ 跳到 ] 之前一条，执行 pc += 1 后到达 ]
+        ItOpcode::LSB(v) => (v as usize).checked_add(1).and_then(|j| it_opcodes.get(j)) == Some(&ItOpcode::RSB(i as u32)),
+        ItOpcode::RSB(v) => i > 0 && it_opcodes.get(v as usize) == Some(&ItOpcode::LSB(i as u32 - 1)),
+        _ => true,
+    })
+}
+
+fn read_bytes<R: Read>(r: &mut R) -> std::io::Result<Vec<u8>> {
+    let len = read_u64(r)?;
+    let mut data = Vec::new();
+    r.take(len).read_to_end(&mut data)?;
+    if data.len() as u64 != len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
     }
+    Ok(data)
 }
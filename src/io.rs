@@ -0,0 +1,43 @@
+use std::io::prelude::*;
+
+/// 引擎执行时的输入输出，没有设置时使用 stdin 和 stdout
+#[derive(Default)]
+pub(crate) struct Io {
+    pub input: Option<Box<dyn Read + Send>>,
+    pub output: Option<Box<dyn Write + Send>>,
+}
+
+impl Io {
+    /// 读取一个字节，输入结束时返回 UnexpectedEof
+    pub fn read_byte(&mut self) -> std::io::Result<u8> {
+        let mut buf = [0; 1];
+        let result = match &mut self.input {
+            Some(input) => input.read_exact(&mut buf),
+            None => std::io::stdin().read_exact(&mut buf),
+        };
+        result.map_err(end_of_input)?;
+        Ok(buf[0])
+    }
+
+    pub fn write_all(&mut self, data: &[u8]) -> std::io::Result<()> {
+        match &mut self.output {
+            Some(output) => output.write_all(data),
+            None => std::io::stdout().write_all(data),
+        }
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.output {
+            Some(output) => output.flush(),
+            None => std::io::stdout().flush(),
+        }
+    }
+}
+
+/// 输入结束的错误使用自己的说明，不依赖标准库的措辞
+pub(crate) fn end_of_input(e: std::io::Error) -> std::io::Error {
+    match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Unexpected end of input"),
+        _ => e,
+    }
+}
@@ -1,3 +1,6 @@
+use super::dump_tape;
+use super::io::Io;
+use super::limit::{Guard, Limits};
 use super::opcode;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -10,6 +13,7 @@ pub enum ItOpcode {
     RSB(u32), // RSB(0): 如果指针指向的单元值不为零，跳转到 0 处
     GETCHAR,
     PUTCHAR,
+    DEBUG,    // 启用调试扩展时的 #
 }
 
 pub struct Code {
@@ -17,16 +21,13 @@ pub struct Code {
 }
 
 impl Code {
-    pub fn from(data: Vec<u8>) -> Result<Self, Box<dyn std::error::Error>> {
-        let data: Vec<&u8> = data.iter()
-            .filter(|x| opcode::OPCODE_DICT.contains(x))
-            .collect();
-
+    /// debug 为 true 时把 # 作为调试指令，否则作为注释
+    pub fn from(data: Vec<u8>, debug: bool) -> Result<Self, Box<dyn std::error::Error>> {
         let mut it_opcodes = Vec::new();
         let mut stack = Vec::new();
 
-        for u in data {
-            match *u {
+        for (_, u) in opcode::opcodes(&data, debug) {
+            match u {
                 opcode::OPCODE_SHL => match it_opcodes.last_mut() {
                     Some(ItOpcode::SHL(v)) => {
                         *v += 1;
@@ -79,10 +80,94 @@ impl Code {
                 opcode::OPCODE_PUTCHAR => {
                     it_opcodes.push(ItOpcode::PUTCHAR);
                 }
+                opcode::OPCODE_DEBUG => {
+                    it_opcodes.push(ItOpcode::DEBUG);
+                }
                 _ => panic!("Unsupported opcode {}", u),
             }
         }
+        if !stack.is_empty() {
+            return Err("Unmatched [".into());
+        }
 
         Ok(Code { it_opcodes })
     }
 }
+
+/// 机器码运行时的上下文，前六个字段由机器码直接读写
+#[repr(C)]
+pub(crate) struct Context {
+    pub steps: u64,    // 已执行的指令数 [ctx + 0]
+    pub budget: u64,   // 在循环回跳处，步数达到该值时调用 poll [ctx + 8]
+    pub ptr: *mut u8,  // 退出时的指针 [ctx + 16]
+    pub cancel: *const u8, // 取消标志，不为零时调用 poll [ctx + 24]
+    pub memory: *mut u8, // 纸带起始地址 [ctx + 32]
+    pub memory_len: usize, // 纸带长度 [ctx + 40]
+    pub debug: usize,    // # 调试指令输出指针左右多少个单元
+    pub guard: Guard,
+    pub error: Option<Box<dyn std::error::Error>>,
+    pub io: Io,
+}
+
+pub(crate) const EXIT_OK: u64 = 0;
+pub(crate) const EXIT_ERROR: u64 = 1;
+
+impl Context {
+    pub fn new(limits: &Limits, memory: &mut [u8], debug: usize, io: Io) -> Self {
+        let memory_len = memory.len();
+        let memory = memory.as_mut_ptr();
+        let guard = Guard::new(limits);
+        Self {
+            steps: 0,
+            budget: guard.budget,
+            ptr: memory,
+            cancel: guard.cancel().map_or(std::ptr::null(), |cancel| cancel.as_ptr()),
+            memory,
+            memory_len,
+            debug,
+            guard,
+            error: None,
+            io,
+        }
+    }
+
+    /// 供 poll 回调使用，返回 EXIT_ERROR 表示需要中止
+    pub fn poll(&mut self, ptr: *mut u8) -> u64 {
+        let pointer = ptr as usize - self.memory as usize;
+        match self.guard.poll(self.steps, pointer) {
+            Ok(()) => {
+                self.budget = self.guard.budget;
+                EXIT_OK
+            }
+            Err(aborted) => {
+                self.error = Some(Box::new(aborted));
+                EXIT_ERROR
+            }
+        }
+    }
+
+    /// 供 > 越过纸带末尾时回调使用，总是返回 EXIT_ERROR
+    pub fn overflow(&mut self) -> u64 {
+        self.error = Some(format!("Pointer moved past the end of the {}-cell tape", self.memory_len).into());
+        EXIT_ERROR
+    }
+
+    /// 供 # 调试指令回调使用
+    pub fn dump(&mut self, ptr: *mut u8) -> u64 {
+        if let Err(e) = self.io.flush() {
+            self.error = Some(e.into());
+            return EXIT_ERROR;
+        }
+        let tape = unsafe { std::slice::from_raw_parts(self.memory, self.memory_len) };
+        dump_tape(tape, ptr as usize - self.memory as usize, self.debug);
+        EXIT_OK
+    }
+
+    /// 根据机器码的返回值得到执行结果
+    pub fn finish(mut self, status: u64) -> Result<(), Box<dyn std::error::Error>> {
+        match status {
+            EXIT_OK => Ok(()),
+            _ => Err(self.error.take().unwrap_or_else(|| "JIT exited abnormally".into())),
+        }
+    }
+}
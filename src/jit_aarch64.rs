@@ -1,124 +1,337 @@
 use std::io::prelude::*;
+use std::time::{Duration, Instant};
 
 use dynasmrt::{dynasm, DynasmApi, DynasmLabelApi};
 
 use super::jit::ItOpcode as ItOpcode;
 use super::jit::Code as Code;
+use super::jit::{Context, EXIT_ERROR, EXIT_OK};
+use super::io::Io;
+use super::limit::Limits;
 
 const MEMERY_SIZE: usize = 65536;
 
-unsafe extern "C" fn getchar(c: *mut u8) {
-    std::io::stdin()
-        .read_exact(std::slice::from_raw_parts_mut(c, 1))
-        .unwrap();
+unsafe extern "C" fn getchar(ctx: *mut Context, c: *mut u8) -> u64 {
+    match (*ctx).io.read_byte() {
+        Ok(v) => {
+            *c = v;
+            EXIT_OK
+        }
+        Err(e) => {
+            (*ctx).error = Some(e.into());
+            EXIT_ERROR
+        }
+    }
+}
+
+unsafe extern "C" fn putchar(ctx: *mut Context, c: *const u8) -> u64 {
+    match (*ctx).io.write_all(std::slice::from_raw_parts(c, 1)) {
+        Ok(()) => {
+            (*ctx).guard.record(*c);
+            EXIT_OK
+        }
+        Err(e) => {
+            (*ctx).error = Some(e.into());
+            EXIT_ERROR
+        }
+    }
 }
 
-unsafe extern "C" fn putchar(c: *const u8) {
-    std::io::stdout()
-        .write_all(std::slice::from_raw_parts(c, 1))
-        .unwrap();
+unsafe extern "C" fn poll(ctx: *mut Context, ptr: *mut u8) -> u64 {
+    (*ctx).poll(ptr)
+}
+
+unsafe extern "C" fn overflow(ctx: *mut Context) -> u64 {
+    (*ctx).overflow()
+}
+
+unsafe extern "C" fn debug(ctx: *mut Context, ptr: *mut u8) -> u64 {
+    (*ctx).dump(ptr)
 }
 
 #[derive(Default)]
-pub struct Interpreter;
+pub struct Interpreter {
+    limits: Limits, // 执行限制
+    debug: Option<usize>, // 启用 # 调试指令时，输出指针左右多少个单元
+    io: Io,
+    memory: Vec<u8>, // 纸带，第一次 run 时分配，多次 run 之间保持不变
+    pointer: usize,  // 上一次 run 结束时的指针
+    compile_time: Duration, // 上一次 run 生成机器码所用的时间
+}
 
 impl Interpreter {
-    pub fn run(&mut self, data: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
-        let code = Code::from(data)?;
+    /// 设置最大指令数、超时时间和取消句柄，超出时返回 limit::Aborted。
+    /// 指令数与 interpreter_it 的计算方式相同，但只在循环回跳处检查
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// 启用 # 调试指令，执行到 # 时把指针和左右 radius 个单元输出到 stderr
+    pub fn with_debug(mut self, radius: usize) -> Self {
+        self.debug = Some(radius);
+        self
+    }
+
+    /// 从 input 读取 , 的输入，默认为 stdin
+    pub fn with_input(mut self, input: impl Read + Send + 'static) -> Self {
+        self.io.input = Some(Box::new(input));
+        self
+    }
+
+    /// 把 . 的输出写到 output，默认为 stdout
+    pub fn with_output(mut self, output: impl Write + Send + 'static) -> Self {
+        self.io.output = Some(Box::new(output));
+        self
+    }
+
+    pub fn tape(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn pointer(&self) -> usize {
+        self.pointer
+    }
+
+    /// 上一次 run 生成机器码所用的时间
+    pub fn compile_time(&self) -> Duration {
+        self.compile_time
+    }
+
+    /// 清空纸带，指针回到 0
+    pub fn reset(&mut self) {
+        self.memory.clear();
+        self.pointer = 0;
+    }
+
+    /// 生成的机器码，可以用 objdump -D -b binary 反汇编，调用的函数地址每次运行都不同
+    pub fn machine_code(&self, data: Vec<u8>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let (exec_buffer, _) = self.compile(data)?;
+        Ok(exec_buffer.to_vec())
+    }
+
+    fn compile(&self, data: Vec<u8>) -> Result<(dynasmrt::ExecutableBuffer, dynasmrt::AssemblyOffset), Box<dyn std::error::Error>> {
+        let code = Code::from(data, self.debug.is_some())?;
         let it_opcodes = code.it_opcodes;
         let mut stack = Vec::new();
+        let counted = self.limits.is_limited(); // 没有执行限制时不生成计数代码
+        let cancellable = self.limits.cancel.is_some();
+        let mut pending: u32 = 0; // 还没有累加到 x21 的指令数
 
         let mut ops = dynasmrt::aarch64::Assembler::new()?;
 
         dynasm!(ops
+            ; .arch aarch64
             ; ->getchar:
-            ; .qword getchar as _
+            ; .qword getchar as *const () as _
             ; ->putchar:
-            ; .qword putchar as _
+            ; .qword putchar as *const () as _
+            ; ->poll:
+            ; .qword poll as *const () as _
+            ; ->debug:
+            ; .qword debug as *const () as _
+            ; ->overflow:
+            ; .qword overflow as *const () as _
         );
 
         let entry_point = ops.offset();
 
+        // x19: 指针, x20: 上下文, x21: 已执行的指令数, x22: 纸带起始地址, x23: 纸带结束地址
         dynasm!(ops
             ; .arch aarch64
-            ; str x30, [sp, #-16]!
-            ; stp x0, x1, [sp, #-16]!
-            ; stp x2, x3, [sp, #-16]!
+            ; stp x29, x30, [sp, #-64]!
+            ; stp x19, x20, [sp, #16]
+            ; stp x21, x22, [sp, #32]
+            ; str x23, [sp, #48]
+            ; mov x20, x0
+            ; mov x19, x1
+            ; ldr x21, [x20]
+            ; ldr x22, [x20, #32]
+            ; ldr x9, [x20, #40]
+            ; add x23, x22, x9
         );
-        
+
+        // 把指针移动的距离放到 x10
+        macro_rules! load_distance {
+            ($v:expr) => {
+                dynasm!(ops
+                    ; movz x10, $v & 0xffff
+                );
+                if $v > 0xffff {
+                    dynasm!(ops
+                        ; movk x10, $v >> 16, lsl 16
+                    );
+                }
+            };
+        }
+
+        macro_rules! flush_steps {
+            () => {
+                while counted && pending > 0 {
+                    let n = pending.min(4095);
+                    dynasm!(ops
+                        ; add x21, x21, n
+                    );
+                    pending -= n;
+                }
+            };
+        }
+
         for opcode in it_opcodes {
+            pending += 1;
             match opcode {
-                ItOpcode::SHL(v) => dynasm!(ops
-                    ; sub x0, x0, v
-                ),
-                ItOpcode::SHR(v) => dynasm!(ops
-                    ; add x0, x0, v
-                ),
+                // 和解释器一样，指针最多移到纸带开头
+                ItOpcode::SHL(v) => {
+                    load_distance!(v);
+                    dynasm!(ops
+                        ; sub x9, x19, x22
+                        ; sub x19, x19, x10
+                        ; cmp x9, x10
+                        ; csel x19, x19, x22, hs
+                    )
+                }
+                // 纸带长度固定，越过末尾时中止
+                ItOpcode::SHR(v) => {
+                    load_distance!(v);
+                    dynasm!(ops
+                        ; sub x9, x23, x19
+                        ; cmp x9, x10
+                        ; b.ls ->overflowed
+                        ; add x19, x19, x10
+                    )
+                }
                 ItOpcode::ADD(v) => dynasm!(ops
-                    ; ldrb w9, [x0]
+                    ; ldrb w9, [x19]
                     ; add w9, w9, v as u32
-                    ; tbz w9, 8, >fine
-                    ;fine:
-                    ; strb w9, [x0]
+                    ; strb w9, [x19]
                 ),
                 ItOpcode::SUB(v) => dynasm!(ops
-                    ; ldrb w9, [x0]
+                    ; ldrb w9, [x19]
                     ; sub w9, w9, v as u32
-                    ; tbz w9, 8, >fine
-                    ;fine:
-                    ; strb w9, [x0]
+                    ; strb w9, [x19]
                 ),
                 ItOpcode::LSB(_) => {
                     let l = ops.new_dynamic_label();
                     let r = ops.new_dynamic_label();
-                    stack.push((l, r));
+                    let skip = ops.new_dynamic_label();
+                    stack.push((l, r, skip));
+                    flush_steps!();
                     dynasm!(ops
-                        ; ldrb w9, [x0]
-                        ; cbz w9, => r
+                        ; ldrb w9, [x19]
+                        ; cbz w9, => skip
                         ; => l
                     )
                 }
                 ItOpcode::RSB(_) => {
-                    let (l, r) = stack.pop().unwrap();
-                    dynasm!(ops
-                        ; ldrb w9, [x0]
-                        ; cbnz w9, => l
-                        ; => r
-                    )
+                    let (l, r, skip) = stack.pop().ok_or("Pop from empty stack")?;
+                    flush_steps!();
+                    if counted {
+                        // 循环回跳时检查执行限制
+                        let poll_label = ops.new_dynamic_label();
+                        dynasm!(ops
+                            ; ldrb w9, [x19]
+                            ; cbz w9, => r
+                            ; ldr x9, [x20, #8]
+                            ; cmp x21, x9
+                            ; b.hs => poll_label
+                        );
+                        if cancellable {
+                            dynasm!(ops
+                                ; ldr x9, [x20, #24]
+                                ; ldrb w9, [x9]
+                                ; cbnz w9, => poll_label
+                            );
+                        }
+                        dynasm!(ops
+                            ; b => l
+                            ; => poll_label
+                            ; str x21, [x20]
+                            ; mov x0, x20
+                            ; mov x1, x19
+                            ; ldr x9, ->poll
+                            ; blr x9
+                            ; cbnz x0, ->exit
+                            ; b => l
+                            // 从 [ 跳过循环时，与 interpreter_it 一样把 ] 也算作一条指令
+                            ; => skip
+                            ; add x21, x21, #1
+                            ; => r
+                        )
+                    } else {
+                        dynasm!(ops
+                            ; ldrb w9, [x19]
+                            ; cbnz w9, => l
+                            ; => skip
+                            ; => r
+                        )
+                    }
                 }
                 ItOpcode::GETCHAR => dynasm!(ops
-                    ; str x0, [sp, #24]
+                    ; mov x0, x20
+                    ; mov x1, x19
                     ; ldr x9, ->getchar
                     ; blr x9
-                    ; mov x9, x1
-                    ; ldp x1, x0, [sp, #16]
-                    ; ldp x2, x3, [sp]
+                    ; cbnz x0, ->exit
                 ),
                 ItOpcode::PUTCHAR => dynasm!(ops
-                    ; str x0, [sp, #24]
+                    ; mov x0, x20
+                    ; mov x1, x19
                     ; ldr x9, ->putchar
                     ; blr x9
-                    ; mov x9, x1
-                    ; ldp x1, x0, [sp, #16]
-                    ; ldp x2, x3, [sp]
+                    ; cbnz x0, ->exit
+                ),
+                ItOpcode::DEBUG => dynasm!(ops
+                    ; mov x0, x20
+                    ; mov x1, x19
+                    ; ldr x9, ->debug
+                    ; blr x9
+                    ; cbnz x0, ->exit
                 ),
             }
         }
+        flush_steps!();
 
         dynasm!(ops
-            ; add sp, sp, #32
-            ; ldr x30, [sp], #16
+            ; mov x0, #0
+            ; ->exit:
+            ; str x21, [x20]
+            ; str x19, [x20, #16]
+            ; ldr x23, [sp, #48]
+            ; ldp x21, x22, [sp, #32]
+            ; ldp x19, x20, [sp, #16]
+            ; ldp x29, x30, [sp], #64
             ; ret
+            ; ->overflowed:
+            ; mov x0, x20
+            ; ldr x9, ->overflow
+            ; blr x9
+            ; b ->exit
         );
 
-        let exec_buffer = ops.finalize().unwrap();
-        let mut memory: Box<[u8]> = vec![0; MEMERY_SIZE].into_boxed_slice();
-        let memory_addr_from = memory.as_mut_ptr();
-        let memory_addr_to = unsafe { memory_addr_from.add(memory.len()) };
-        let fun: fn(memory_addr_from: *mut u8, memory_addr_to: *mut u8) =
+        let exec_buffer = ops.finalize().map_err(|_| "Failed to finalize machine code")?;
+        Ok((exec_buffer, entry_point))
+    }
+
+    /// 在上一次 run 结束时的纸带和指针上执行程序
+    pub fn run(&mut self, data: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+        let start = Instant::now();
+        let (exec_buffer, entry_point) = self.compile(data)?;
+        self.compile_time = start.elapsed();
+        if self.memory.is_empty() {
+            self.memory = vec![0; MEMERY_SIZE];
+        }
+        let io = std::mem::take(&mut self.io);
+        let mut ctx = Context::new(&self.limits, &mut self.memory, self.debug.unwrap_or_default(), io);
+        let fun: extern "C" fn(ctx: *mut Context, memory_addr_from: *mut u8) -> u64 =
             unsafe { std::mem::transmute(exec_buffer.ptr(entry_point)) };
-        fun(memory_addr_from, memory_addr_to);
+        // 从上一次 run 结束时的指针继续
+        let memory_addr_from = unsafe { ctx.memory.add(self.pointer) };
+        let status = fun(&mut ctx, memory_addr_from);
+        self.pointer = ctx.ptr as usize - ctx.memory as usize;
 
+        self.io = std::mem::take(&mut ctx.io);
+        ctx.finish(status)?;
+        self.io.flush()?;
         Ok(())
     }
 }
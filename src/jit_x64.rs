@@ -1,104 +1,312 @@
 use std::io::prelude::*;
+use std::time::{Duration, Instant};
 
 use dynasmrt::{dynasm, DynasmApi, DynasmLabelApi};
 
 use super::jit::ItOpcode as ItOpcode;
 use super::jit::Code as Code;
+use super::jit::{Context, EXIT_ERROR, EXIT_OK};
+use super::io::Io;
+use super::limit::Limits;
 
 const MEMERY_SIZE: usize = 65536;
 
-unsafe extern "sysv64" fn getchar(c: *mut u8) {
-    std::io::stdin()
-        .read_exact(std::slice::from_raw_parts_mut(c, 1))
-        .unwrap();
+unsafe extern "sysv64" fn getchar(ctx: *mut Context, c: *mut u8) -> u64 {
+    match (*ctx).io.read_byte() {
+        Ok(v) => {
+            *c = v;
+            EXIT_OK
+        }
+        Err(e) => {
+            (*ctx).error = Some(e.into());
+            EXIT_ERROR
+        }
+    }
+}
+
+unsafe extern "sysv64" fn putchar(ctx: *mut Context, c: *const u8) -> u64 {
+    match (*ctx).io.write_all(std::slice::from_raw_parts(c, 1)) {
+        Ok(()) => {
+            (*ctx).guard.record(*c);
+            EXIT_OK
+        }
+        Err(e) => {
+            (*ctx).error = Some(e.into());
+            EXIT_ERROR
+        }
+    }
+}
+
+unsafe extern "sysv64" fn poll(ctx: *mut Context, ptr: *mut u8) -> u64 {
+    (*ctx).poll(ptr)
+}
+
+unsafe extern "sysv64" fn overflow(ctx: *mut Context) -> u64 {
+    (*ctx).overflow()
 }
 
-unsafe extern "sysv64" fn putchar(c: *const u8) {
-    std::io::stdout()
-        .write_all(std::slice::from_raw_parts(c, 1))
-        .unwrap();
+unsafe extern "sysv64" fn debug(ctx: *mut Context, ptr: *mut u8) -> u64 {
+    (*ctx).dump(ptr)
 }
 
 #[derive(Default)]
-pub struct Interpreter;
+pub struct Interpreter {
+    limits: Limits, // 执行限制
+    debug: Option<usize>, // 启用 # 调试指令时，输出指针左右多少个单元
+    io: Io,
+    memory: Vec<u8>, // 纸带，第一次 run 时分配，多次 run 之间保持不变
+    pointer: usize,  // 上一次 run 结束时的指针
+    compile_time: Duration, // 上一次 run 生成机器码所用的时间
+}
 
 impl Interpreter {
-    pub fn run(&mut self, data: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
-        let code = Code::from(data)?;
+    /// 设置最大指令数、超时时间和取消句柄，超出时返回 limit::Aborted。
+    /// 指令数与 interpreter_it 的计算方式相同，但只在循环回跳处检查
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// 启用 # 调试指令，执行到 # 时把指针和左右 radius 个单元输出到 stderr
+    pub fn with_debug(mut self, radius: usize) -> Self {
+        self.debug = Some(radius);
+        self
+    }
+
+    /// 从 input 读取 , 的输入，默认为 stdin
+    pub fn with_input(mut self, input: impl Read + Send + 'static) -> Self {
+        self.io.input = Some(Box::new(input));
+        self
+    }
+
+    /// 把 . 的输出写到 output，默认为 stdout
+    pub fn with_output(mut self, output: impl Write + Send + 'static) -> Self {
+        self.io.output = Some(Box::new(output));
+        self
+    }
+
+    pub fn tape(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn pointer(&self) -> usize {
+        self.pointer
+    }
+
+    /// 上一次 run 生成机器码所用的时间
+    pub fn compile_time(&self) -> Duration {
+        self.compile_time
+    }
+
+    /// 清空纸带，指针回到 0
+    pub fn reset(&mut self) {
+        self.memory.clear();
+        self.pointer = 0;
+    }
+
+    /// 生成的机器码，可以用 objdump -D -b binary 反汇编，调用的函数地址每次运行都不同
+    pub fn machine_code(&self, data: Vec<u8>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let (exec_buffer, _) = self.compile(data)?;
+        Ok(exec_buffer.to_vec())
+    }
+
+    fn compile(&self, data: Vec<u8>) -> Result<(dynasmrt::ExecutableBuffer, dynasmrt::AssemblyOffset), Box<dyn std::error::Error>> {
+        let code = Code::from(data, self.debug.is_some())?;
         let it_opcodes = code.it_opcodes;
         let mut stack = Vec::new();
+        let counted = self.limits.is_limited(); // 没有执行限制时不生成计数代码
+        let cancellable = self.limits.cancel.is_some();
+        let mut pending: i32 = 0; // 还没有累加到 r13 的指令数
 
         let mut ops = dynasmrt::x64::Assembler::new()?;
         let entry_point = ops.offset();
 
+        // rbx: 指针, r12: 上下文, r13: 已执行的指令数, r14: 纸带起始地址, r15: 纸带结束地址
         dynasm!(ops
             ; .arch x64
-            ; mov rcx, rdi
-            ; sub rsp, BYTE 0x28
+            ; push rbp
+            ; push rbx
+            ; push r12
+            ; push r13
+            ; push r14
+            ; push r15
+            ; sub rsp, BYTE 0x08
+            ; mov r12, rdi
+            ; mov rbx, rsi
+            ; mov r13, QWORD [r12]
+            ; mov r14, QWORD [r12 + 32]
+            ; mov r15, r14
+            ; add r15, QWORD [r12 + 40]
         );
-        
+
+        macro_rules! flush_steps {
+            () => {
+                if counted && pending > 0 {
+                    dynasm!(ops
+                        ; add r13, pending
+                    );
+                    pending = 0;
+                }
+            };
+        }
+
         for opcode in it_opcodes {
+            pending += 1;
             match opcode {
+                // 和解释器一样，指针最多移到纸带开头
                 ItOpcode::SHL(v) => dynasm!(ops
-                    ; sub rcx, v as i32
+                    ; mov rax, rbx
+                    ; sub rax, r14
+                    ; sub rbx, v as i32
+                    ; cmp rax, v as i32
+                    ; cmovb rbx, r14
                 ),
+                // 纸带长度固定，越过末尾时中止
                 ItOpcode::SHR(v) => dynasm!(ops
-                    ; add rcx, v as i32
+                    ; lea rax, [rbx + v as i32]
+                    ; cmp rax, r15
+                    ; jae ->overflow
+                    ; mov rbx, rax
                 ),
                 ItOpcode::ADD(v) => dynasm!(ops
-                    ; add BYTE [rcx], v as i8
+                    ; add BYTE [rbx], v as i8
                 ),
                 ItOpcode::SUB(v) => dynasm!(ops
-                    ; sub BYTE [rcx], v as i8
+                    ; sub BYTE [rbx], v as i8
                 ),
                 ItOpcode::LSB(_) => {
                     let l = ops.new_dynamic_label();
                     let r = ops.new_dynamic_label();
-                    stack.push((l, r));
+                    let skip = ops.new_dynamic_label();
+                    stack.push((l, r, skip));
+                    flush_steps!();
                     dynasm!(ops
-                        ; cmp BYTE [rcx], 0
-                        ; jz => r
+                        ; cmp BYTE [rbx], 0
+                        ; jz => skip
                         ; => l
                     )
                 },
                 ItOpcode::RSB(_) => {
-                    let (l, r) = stack.pop().unwrap();
-                    dynasm!(ops
-                        ; cmp BYTE [rcx], 0
-                        ; jnz => l
-                        ; => r
-                    )
+                    let (l, r, skip) = stack.pop().ok_or("Pop from empty stack")?;
+                    flush_steps!();
+                    if counted {
+                        // 循环回跳时检查执行限制
+                        let poll_label = ops.new_dynamic_label();
+                        dynasm!(ops
+                            ; cmp BYTE [rbx], 0
+                            ; jz => r
+                            ; cmp r13, QWORD [r12 + 8]
+                            ; jae => poll_label
+                        );
+                        if cancellable {
+                            dynasm!(ops
+                                ; mov rax, QWORD [r12 + 24]
+                                ; cmp BYTE [rax], 0
+                                ; jnz => poll_label
+                            );
+                        }
+                        dynasm!(ops
+                            ; jmp => l
+                            ; => poll_label
+                            ; mov QWORD [r12], r13
+                            ; mov rdi, r12
+                            ; mov rsi, rbx
+                            ; mov rax, QWORD poll as *const () as _
+                            ; call rax
+                            ; test rax, rax
+                            ; jnz ->exit
+                            ; jmp => l
+                            // 从 [ 跳过循环时，与 interpreter_it 一样把 ] 也算作一条指令
+                            ; => skip
+                            ; add r13, 1
+                            ; => r
+                        )
+                    } else {
+                        dynasm!(ops
+                            ; cmp BYTE [rbx], 0
+                            ; jnz => l
+                            ; => skip
+                            ; => r
+                        )
+                    }
                 },
                 ItOpcode::GETCHAR => dynasm!(ops
-                    ; mov r12, rcx
-                    ; mov rdi, rcx
-                    ; mov rax, QWORD getchar as _
+                    ; mov rdi, r12
+                    ; mov rsi, rbx
+                    ; mov rax, QWORD getchar as *const () as _
                     ; call rax
-                    ; mov rcx, r12
+                    ; test rax, rax
+                    ; jnz ->exit
                 ),
                 ItOpcode::PUTCHAR => dynasm!(ops
-                    ; mov r12, rcx
-                    ; mov rdi, rcx
-                    ; mov rax, QWORD putchar as _
+                    ; mov rdi, r12
+                    ; mov rsi, rbx
+                    ; mov rax, QWORD putchar as *const () as _
                     ; call rax
-                    ; mov rcx, r12
+                    ; test rax, rax
+                    ; jnz ->exit
+                ),
+                ItOpcode::DEBUG => dynasm!(ops
+                    ; mov rdi, r12
+                    ; mov rsi, rbx
+                    ; mov rax, QWORD debug as *const () as _
+                    ; call rax
+                    ; test rax, rax
+                    ; jnz ->exit
                 ),
             }
         }
 
+        if counted && pending > 0 {
+            dynasm!(ops
+                ; add r13, pending
+            );
+        }
+
         dynasm!(ops
-            ; add rsp, BYTE 0x28
+            ; xor eax, eax
+            ; ->exit:
+            ; mov QWORD [r12], r13
+            ; mov QWORD [r12 + 16], rbx
+            ; add rsp, BYTE 0x08
+            ; pop r15
+            ; pop r14
+            ; pop r13
+            ; pop r12
+            ; pop rbx
+            ; pop rbp
             ; ret
+            ; ->overflow:
+            ; mov rdi, r12
+            ; mov rax, QWORD overflow as *const () as _
+            ; call rax
+            ; jmp ->exit
         );
 
-        let exec_buffer = ops.finalize().unwrap();
-        let mut memory: Box<[u8]> = vec![0; MEMERY_SIZE].into_boxed_slice();
-        let memory_addr_from = memory.as_mut_ptr();
-        let memory_addr_to = unsafe { memory_addr_from.add(memory.len()) };
-        let fun: fn(memory_addr_from: *mut u8, memory_addr_to: *mut u8) =
+        let exec_buffer = ops.finalize().map_err(|_| "Failed to finalize machine code")?;
+        Ok((exec_buffer, entry_point))
+    }
+
+    /// 在上一次 run 结束时的纸带和指针上执行程序
+    pub fn run(&mut self, data: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+        let start = Instant::now();
+        let (exec_buffer, entry_point) = self.compile(data)?;
+        self.compile_time = start.elapsed();
+        if self.memory.is_empty() {
+            self.memory = vec![0; MEMERY_SIZE];
+        }
+        let io = std::mem::take(&mut self.io);
+        let mut ctx = Context::new(&self.limits, &mut self.memory, self.debug.unwrap_or_default(), io);
+        let fun: extern "sysv64" fn(ctx: *mut Context, memory_addr_from: *mut u8) -> u64 =
             unsafe { std::mem::transmute(exec_buffer.ptr(entry_point)) };
-        fun(memory_addr_from, memory_addr_to);
+        // 从上一次 run 结束时的指针继续
+        let memory_addr_from = unsafe { ctx.memory.add(self.pointer) };
+        let status = fun(&mut ctx, memory_addr_from);
+        self.pointer = ctx.ptr as usize - ctx.memory as usize;
 
+        self.io = std::mem::take(&mut ctx.io);
+        ctx.finish(status)?;
+        self.io.flush()?;
         Ok(())
     }
 }
@@ -1,15 +1,37 @@
+#![allow(clippy::upper_case_acronyms)]
+
 pub mod opcode;
+pub mod engine;
+pub mod differential;
+pub mod bench;
 pub mod interpreter;
 pub mod interpreter_it;
+pub mod debugger;
 pub mod jit;
+pub mod limit;
+pub mod profile;
+pub mod coverage;
+pub mod trace;
+pub mod protocol;
+pub mod dap;
+pub mod lsp;
+pub mod repl;
+pub mod formatter;
+mod io;
+
+#[cfg(feature = "tui")]
+pub mod tui;
 
-#[cfg(all(target_arch = "aarch64"))]
+#[cfg(target_arch = "aarch64")]
 pub mod jit_aarch64;
 
 #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
 pub mod jit_x64;
 
-#[cfg(all(target_arch = "aarch64"))]
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub mod tiered;
+
+#[cfg(target_arch = "aarch64")]
 pub use jit_aarch64::*;
 
 #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
@@ -17,14 +39,44 @@ pub use jit_x64::*;
 
 use std::io::prelude::*;
 
+/// 读取第一个命令行参数指定的程序
 pub fn parse_data() -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let args: Vec<String> = std::env::args().collect();
-
-    assert!(args.len() >= 2);
+    let path = std::env::args().nth(1).ok_or("Usage: <binary> PROGRAM (- reads it from stdin)")?;
+    read_program(&path)
+}
 
-    let mut f = std::fs::File::open(&args[1])?;
+/// 读取程序，path 为 - 时从 stdin 读取
+pub fn read_program(path: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
     let mut data: Vec<u8> = Vec::new();
-    f.read_to_end(&mut data)?;
+    if path == "-" {
+        std::io::stdin().read_to_end(&mut data)?;
+    } else {
+        let mut f = std::fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        f.read_to_end(&mut data)?;
+    }
 
     Ok(data)
 }
+
+/// 纸带上指针左右各 radius 个单元，分三行显示下标、数值和 ASCII
+pub fn format_tape(tape: &[u8], pointer: usize, radius: usize) -> String {
+    let from = pointer.saturating_sub(radius);
+    let to = pointer.saturating_add(radius).saturating_add(1).min(tape.len());
+
+    let mut index = String::new();
+    let mut value = String::new();
+    let mut ascii = String::new();
+    for (i, c) in tape.iter().enumerate().take(to).skip(from) {
+        let mark = if i == pointer { '>' } else { ' ' };
+        index.push_str(&format!("{}{:>5}", mark, i));
+        value.push_str(&format!(" {:>5}", c));
+        let shown = if c.is_ascii_graphic() { *c as char } else { '.' };
+        ascii.push_str(&format!(" {:>5}", shown));
+    }
+    format!("{}\n{}\n{}", index, value, ascii)
+}
+
+/// 执行到 # 调试指令时，把指针和附近的纸带输出到 stderr
+pub fn dump_tape(tape: &[u8], pointer: usize, radius: usize) {
+    eprintln!("# pointer {}\n{}", pointer, format_tape(tape, pointer, radius));
+}
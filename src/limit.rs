@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: u64 = 1 << 16; // 设置了超时时，每执行这么多步检查一次时间
+const OUTPUT_TAIL: usize = 64 * 1024; // 中止时最多返回最后这么多字节的输出
+
+/// 取消执行的句柄，可以克隆后在其他线程中调用 cancel。
+/// 引擎在循环回跳处检查，被取消时返回 AbortReason::Cancelled
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// 标志所在的地址，供机器码直接读取
+    pub(crate) fn as_ptr(&self) -> *const u8 {
+        self.0.as_ptr() as *const u8
+    }
+}
+
+/// 执行限制
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+    pub max_steps: Option<u64>,        // 最多执行的指令数
+    pub timeout: Option<Duration>,     // 最长执行时间
+    pub cancel: Option<CancelToken>,   // 取消执行
+}
+
+impl Limits {
+    pub fn max_steps(mut self, max_steps: u64) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// 是否设置了任意一种限制
+    pub fn is_limited(&self) -> bool {
+        self.max_steps.is_some() || self.timeout.is_some() || self.cancel.is_some()
+    }
+}
+
+/// 中止执行的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbortReason {
+    StepLimit,
+    Timeout,
+    Cancelled,
+}
+
+impl fmt::Display for AbortReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbortReason::StepLimit => write!(f, "step limit exceeded"),
+            AbortReason::Timeout => write!(f, "timeout"),
+            AbortReason::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+/// 程序因超出执行限制被中止
+#[derive(Debug)]
+pub struct Aborted {
+    pub reason: AbortReason,
+    pub pointer: usize, // 中止时的指针
+    pub steps: u64,     // 中止前已执行的指令数
+    pub output: Vec<u8>, // 中止前最后输出的至多 64 KiB 内容，更早的输出已经写到输出流
+}
+
+impl fmt::Display for Aborted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Aborted ({}) after {} steps at pointer {}", self.reason, self.steps, self.pointer)
+    }
+}
+
+impl std::error::Error for Aborted {}
+
+/// 在执行过程中检查执行限制。
+/// 引擎每执行一步把步数加一，步数达到 budget 时调用 poll
+pub(crate) struct Guard {
+    max_steps: u64,
+    deadline: Option<Instant>,
+    cancel: Option<CancelToken>,
+    pub budget: u64, // 下一次调用 poll 时的步数
+    record: bool,    // 是否保存输出，中止时返回
+    output: VecDeque<u8>, // 最后 OUTPUT_TAIL 个字节的输出
+}
+
+impl Guard {
+    pub fn new(limits: &Limits) -> Self {
+        let mut guard = Self {
+            max_steps: limits.max_steps.unwrap_or(u64::MAX),
+            deadline: limits.timeout.map(|timeout| Instant::now() + timeout),
+            cancel: limits.cancel.clone(),
+            budget: 0,
+            record: limits.is_limited(),
+            output: VecDeque::new(),
+        };
+        guard.budget = guard.next_budget(0);
+        guard
+    }
+
+    fn next_budget(&self, steps: u64) -> u64 {
+        match self.deadline {
+            Some(_) => self.max_steps.min(steps.saturating_add(POLL_INTERVAL)),
+            None => self.max_steps,
+        }
+    }
+
+    /// 保存输出，只保留最后 OUTPUT_TAIL 个字节
+    pub fn record(&mut self, c: u8) {
+        if self.record {
+            if self.output.len() == OUTPUT_TAIL {
+                self.output.pop_front();
+            }
+            self.output.push_back(c);
+        }
+    }
+
+    pub fn cancel(&self) -> Option<&CancelToken> {
+        self.cancel.as_ref()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|cancel| cancel.is_cancelled())
+    }
+
+    fn abort(&mut self, reason: AbortReason, steps: u64, pointer: usize) -> Aborted {
+        Aborted {
+            reason,
+            pointer,
+            steps,
+            output: std::mem::take(&mut self.output).into(),
+        }
+    }
+
+    /// 检查是否超出限制，没有超出时计算下一次检查的步数
+    pub fn poll(&mut self, steps: u64, pointer: usize) -> Result<(), Aborted> {
+        let reason = if self.is_cancelled() {
+            Some(AbortReason::Cancelled)
+        } else if steps >= self.max_steps {
+            Some(AbortReason::StepLimit)
+        } else if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            Some(AbortReason::Timeout)
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => Err(self.abort(reason, steps, pointer)),
+            None => {
+                self.budget = self.next_budget(steps);
+                Ok(())
+            }
+        }
+    }
+
+    /// 在循环回跳处检查是否被取消
+    pub fn check_cancel(&mut self, steps: u64, pointer: usize) -> Result<(), Aborted> {
+        if self.is_cancelled() {
+            return Err(self.abort(AbortReason::Cancelled, steps, pointer));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,270 @@
+use super::debugger::SourceMap;
+use super::interpreter_it::{parse, ItOpcode, Parsed};
+use super::opcode;
+use super::profile::{classify, LoopKind};
+use super::protocol::{read_message, write_message, InvalidMessage};
+
+use std::collections::HashMap;
+use std::io::prelude::*;
+use std::ops::Range;
+
+use serde_json::{json, Value};
+
+const SEVERITY_ERROR: u64 = 1;
+const SYMBOL_KIND_FUNCTION: u64 = 12; // 循环作为函数显示在大纲中
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// 通过 input/output 提供 Language Server Protocol 服务: 括号不配对的诊断、悬停显示中间表示、
+/// 跳转到配对的括号、循环的折叠范围和顶层循环的大纲。位置按 UTF-16 计算
+pub fn serve<R: BufRead, W: Write>(mut input: R, mut output: W) -> Result<(), Box<dyn std::error::Error>> {
+    let mut documents: HashMap<String, Document> = HashMap::new();
+    let mut shutdown = false;
+
+    loop {
+        // 无效的消息回复 ParseError 后继续读取，只有输入结束或者 I/O 错误时退出
+        let message = match read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) if e.is::<InvalidMessage>() => {
+                let error = json!({ "code": PARSE_ERROR, "message": e.to_string() });
+                write_message(&mut output, &json!({ "jsonrpc": "2.0", "id": null, "error": error }))?;
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "foldingRangeProvider": true,
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "bf", "version": env!("CARGO_PKG_VERSION") },
+            }),
+            "shutdown" => {
+                shutdown = true;
+                Value::Null
+            }
+            "exit" => return if shutdown { Ok(()) } else { Err("Exit before shutdown".into()) },
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let text = match method {
+                    "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+                    // 全量同步，最后一次修改是完整的文本
+                    _ => params["contentChanges"].as_array().and_then(|c| c.last()).and_then(|c| c["text"].as_str()),
+                };
+                let document = Document::new(text.unwrap_or_default().to_string());
+                let diagnostics = document.diagnostics();
+                documents.insert(uri.clone(), document);
+                notify(&mut output, "textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": diagnostics }))?;
+                continue;
+            }
+            "textDocument/didClose" => {
+                documents.remove(&uri);
+                notify(&mut output, "textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": [] }))?;
+                continue;
+            }
+            "textDocument/hover" | "textDocument/definition" | "textDocument/foldingRange" | "textDocument/documentSymbol" => {
+                match documents.get(&uri) {
+                    Some(document) => match method {
+                        "textDocument/hover" => document.hover(&params["position"]),
+                        "textDocument/definition" => document.definition(&uri, &params["position"]),
+                        "textDocument/foldingRange" => document.folding_ranges(),
+                        _ => document.symbols(),
+                    },
+                    None => Value::Null,
+                }
+            }
+            _ => {
+                // 不支持的请求返回错误，不支持的通知直接忽略
+                if !message["id"].is_null() {
+                    let error = json!({ "code": METHOD_NOT_FOUND, "message": format!("Unsupported method {}", method) });
+                    write_message(&mut output, &json!({ "jsonrpc": "2.0", "id": message["id"], "error": error }))?;
+                }
+                continue;
+            }
+        };
+
+        if !message["id"].is_null() {
+            write_message(&mut output, &json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }))?;
+        }
+    }
+    Ok(())
+}
+
+fn notify<W: Write>(output: &mut W, method: &str, params: Value) -> std::io::Result<()> {
+    write_message(output, &json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+}
+
+/// 打开的文档和它的解析结果
+struct Document {
+    text: String,
+    source_map: SourceMap,
+    parsed: Parsed,
+    pairs: Vec<(usize, usize)>, // 配对的 [ 和 ] 的指令下标，按 [ 排序
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let source_map = SourceMap::new(text.as_bytes());
+        let parsed = parse(text.as_bytes(), false);
+
+        let mut pairs = Vec::new();
+        let mut stack = Vec::new();
+        for (pc, opcode) in parsed.opcodes.iter().enumerate() {
+            match opcode {
+                ItOpcode::LSB(_) => stack.push(pc),
+                ItOpcode::RSB(_) => {
+                    if let Some(lsb) = stack.pop() {
+                        pairs.push((lsb, pc));
+                    }
+                }
+                _ => {}
+            }
+        }
+        pairs.sort_unstable();
+
+        Self { text, source_map, parsed, pairs }
+    }
+
+    /// 偏移对应的 LSP 位置，行列号从 0 开始，列按 UTF-16 计算
+    fn position(&self, offset: usize) -> Value {
+        let (line, _) = self.source_map.position(offset);
+        let start = self.source_map.line(line).map_or(0, |range| range.start);
+        let character = self.text[start..offset].encode_utf16().count();
+        json!({ "line": line - 1, "character": character })
+    }
+
+    fn range(&self, range: Range<usize>) -> Value {
+        json!({ "start": self.position(range.start), "end": self.position(range.end) })
+    }
+
+    /// LSP 位置对应的偏移
+    fn offset(&self, position: &Value) -> Option<usize> {
+        let line = self.source_map.line((position["line"].as_u64()? as usize).checked_add(1)?)?;
+        let character = position["character"].as_u64()? as usize;
+        let mut units = 0;
+        for (i, c) in self.text[line.clone()].char_indices() {
+            if units >= character {
+                return Some(line.start + i);
+            }
+            units += c.len_utf16();
+        }
+        Some(line.end)
+    }
+
+    /// 光标所在的指令
+    fn instruction(&self, position: &Value) -> Option<usize> {
+        let offset = self.offset(position)?;
+        if !opcode::is_opcode(*self.text.as_bytes().get(offset)?, false) {
+            return None;
+        }
+        self.parsed.spans.iter().position(|span| span.contains(&offset))
+    }
+
+    /// 和第 pc 条指令配对的括号
+    fn matching(&self, pc: usize) -> Option<usize> {
+        self.pairs.iter().find_map(|&(lsb, rsb)| {
+            if pc == lsb {
+                Some(rsb)
+            } else if pc == rsb {
+                Some(lsb)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn diagnostics(&self) -> Vec<Value> {
+        self.parsed
+            .unmatched
+            .iter()
+            .map(|&i| {
+                let message = if self.text.as_bytes()[i] == opcode::OPCODE_LSB {
+                    "Unmatched [: no ] closes this loop"
+                } else {
+                    "Unmatched ]: no [ opens this loop"
+                };
+                json!({ "range": self.range(i..i + 1), "severity": SEVERITY_ERROR, "source": "bf", "message": message })
+            })
+            .collect()
+    }
+
+    /// 循环的说明，例如 multiply loop
+    fn describe_loop(&self, lsb: usize, rsb: usize) -> String {
+        match classify(&self.parsed.opcodes[lsb + 1..rsb]) {
+            LoopKind::General => "loop".to_string(),
+            kind => format!("{} loop", kind),
+        }
+    }
+
+    fn hover(&self, position: &Value) -> Value {
+        let Some(pc) = self.instruction(position) else {
+            return Value::Null;
+        };
+        let opcode = self.parsed.opcodes[pc];
+        let value = match opcode {
+            ItOpcode::LSB(_) | ItOpcode::RSB(_) => match self.matching(pc) {
+                Some(other) => {
+                    let (lsb, rsb) = (pc.min(other), pc.max(other));
+                    let body = rsb - lsb - 1;
+                    format!("{}, {} instruction{}", self.describe_loop(lsb, rsb), body, if body == 1 { "" } else { "s" })
+                }
+                None => "unmatched [".to_string(),
+            },
+            _ => format!("`{:?}`", opcode),
+        };
+        json!({
+            "contents": { "kind": "markdown", "value": value },
+            "range": self.range(self.parsed.spans[pc].clone()),
+        })
+    }
+
+    fn definition(&self, uri: &str, position: &Value) -> Value {
+        match self.instruction(position).and_then(|pc| self.matching(pc)) {
+            Some(other) => json!({ "uri": uri, "range": self.range(self.parsed.spans[other].clone()) }),
+            None => Value::Null,
+        }
+    }
+
+    fn folding_ranges(&self) -> Value {
+        let ranges: Vec<Value> = self
+            .pairs
+            .iter()
+            .filter_map(|&(lsb, rsb)| {
+                let (start, _) = self.source_map.position(self.parsed.spans[lsb].start);
+                let (end, _) = self.source_map.position(self.parsed.spans[rsb].start);
+                (end > start).then(|| json!({ "startLine": start - 1, "endLine": end - 1, "kind": "region" }))
+            })
+            .collect();
+        json!(ranges)
+    }
+
+    /// 顶层循环的大纲
+    fn symbols(&self) -> Value {
+        let mut symbols = Vec::new();
+        let mut end = 0; // 上一个顶层循环的 ]
+        for &(lsb, rsb) in &self.pairs {
+            if !symbols.is_empty() && lsb < end {
+                continue;
+            }
+            end = rsb;
+            let start = self.parsed.spans[lsb].start;
+            let (line, column) = self.source_map.position(start);
+            symbols.push(json!({
+                "name": format!("loop {}:{}", line, column),
+                "detail": self.describe_loop(lsb, rsb),
+                "kind": SYMBOL_KIND_FUNCTION,
+                "range": self.range(start..self.parsed.spans[rsb].end),
+                "selectionRange": self.range(self.parsed.spans[lsb].clone()),
+            }));
+        }
+        json!(symbols)
+    }
+}
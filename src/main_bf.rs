@@ -0,0 +1,512 @@
+use brainfuck_toy::debugger::{Debugger, SourceMap};
+use brainfuck_toy::engine::{Engine, Options};
+use brainfuck_toy::formatter::Formatter;
+use brainfuck_toy::interpreter_it::{self, ItOpcode};
+use brainfuck_toy::bench;
+use brainfuck_toy::repl::Repl;
+use brainfuck_toy::{dap, lsp, read_program};
+
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::process::ExitCode;
+use std::time::Duration;
+
+const USAGE: &str = "Usage: bf <COMMAND> [OPTIONS]
+
+Commands:
+  run      Run a program
+  compile  Print the IR of a program, or the machine code generated by the JIT
+  debug    Step through a program in the debugger
+  fmt      Reindent a program by loop depth
+  check    Check that the brackets of a program are balanced
+  bench    Compare the engines on one or more programs
+  repl     Run brainfuck line by line on a persistent tape
+  dap      Serve the Debug Adapter Protocol over stdio
+  lsp      Serve the Language Server Protocol over stdio
+
+Run `bf <COMMAND> --help` for the options of a command.
+PROGRAM is a file name, or - to read the program from stdin. Every command also accepts -e CODE instead.";
+
+const RUN_USAGE: &str = "Usage: bf run [OPTIONS] (PROGRAM | -e CODE)
+
+Options:
+  --engine ENGINE   naive, it, tiered or jit (default: jit where supported, otherwise it)
+  --input FILE      Read the input of , from FILE instead of stdin
+  --input-string S  Use S as the input of ,
+  --output FILE     Write the output to FILE instead of stdout
+  --max-steps N     Abort after N instructions
+  --timeout SECS    Abort after SECS seconds
+  --dump RADIUS     Treat # as an instruction printing RADIUS cells around the pointer to stderr
+  -h, --help        Print this help
+
+Tracing, profiling, coverage and checkpoints are not available here: they stay on the interpreter_it
+binary (and interpreter for --trace).";
+
+const COMPILE_USAGE: &str = "Usage: bf compile [OPTIONS] (PROGRAM | -e CODE)
+
+Options:
+  --engine ENGINE   it prints the IR, jit the generated machine code (default: it)
+  -o FILE           Write the raw machine code to FILE instead of a hex dump
+  -h, --help        Print this help";
+
+const DEBUG_USAGE: &str = "Usage: bf debug [OPTIONS] (PROGRAM | -e CODE)
+
+Options:
+  --input FILE      Queue the contents of FILE as the program's input
+  --input-string S  Queue S as the program's input
+  -h, --help        Print this help
+
+Type help in the debugger for its commands.";
+
+const FMT_USAGE: &str = "Usage: bf fmt [OPTIONS] (PROGRAM | -e CODE)
+
+Options:
+  --width N          Wrap lines at N columns, indentation included (default: 80)
+  --indent N         Indent loop bodies by N spaces per level, at most the width (default: 4)
+  --strip-comments   Drop comments and blank lines
+  --dump             Keep # as an instruction, as run --dump treats it
+  -h, --help         Print this help
+
+Loops without nested loops stay on one line when they fit, runs of the same instruction are only
+split when longer than a line, and comments are kept on lines of their own.";
+
+const CHECK_USAGE: &str = "Usage: bf check (PROGRAM | -e CODE)
+
+Reports unmatched brackets with their line and column and exits with status 1 if there are any.";
+
+const BENCH_USAGE: &str = "Usage: bf bench [OPTIONS] [PROGRAM... | -e CODE]
+
+Options:
+  --engine ENGINE   Only time ENGINE, can be repeated (default: every engine the platform supports)
+  --runs N          Number of runs per engine (default: 3)
+  --input FILE      Read the input of , from FILE, reread on every run
+  --input-string S  Use S as the input of , on every run
+  --json            Print the results as JSON
+  -h, --help        Print this help
+
+Without PROGRAM, times bf/mandelbrot.bf and bf/life.bf. The programs' output is discarded.
+Speedups are relative to the first engine; each engine runs with its own fixed optimizations.";
+
+const REPL_USAGE: &str = "Usage: bf repl [PROGRAM | -e CODE]
+
+Runs PROGRAM first if given, then reads brainfuck line by line. Every line runs on the same tape and pointer.
+Type :help in the REPL for its commands.";
+
+const DEFAULT_RUNS: usize = 3;
+
+/// 命令行用法错误，输出用法后以状态码 2 退出
+#[derive(Debug)]
+struct UsageError {
+    message: String,
+    usage: &'static str,
+}
+
+impl std::fmt::Display for UsageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// 程序的来源
+enum Program {
+    Path(String),   // 文件，- 表示 stdin
+    Inline(String), // -e 指定的代码
+}
+
+/// , 的输入
+enum Input {
+    File(String),
+    String(String),
+}
+
+/// 一个子命令的参数
+struct Args {
+    args: std::vec::IntoIter<String>,
+    usage: &'static str,
+    program: Option<Program>,
+    input: Option<Input>,
+    output: Option<String>,
+}
+
+impl Args {
+    fn new(args: Vec<String>, usage: &'static str) -> Self {
+        Self { args: args.into_iter(), usage, program: None, input: None, output: None }
+    }
+
+    fn error(&self, message: impl Into<String>) -> UsageError {
+        UsageError { message: message.into(), usage: self.usage }
+    }
+
+    /// 选项的值
+    fn value(&mut self, flag: &str) -> Result<String, UsageError> {
+        self.args.next().ok_or_else(|| self.error(format!("{} needs a value", flag)))
+    }
+
+    fn parse<T>(&mut self, flag: &str) -> Result<T, UsageError>
+    where
+        T: std::str::FromStr,
+        T::Err: std::fmt::Display,
+    {
+        let value = self.value(flag)?;
+        value.parse().map_err(|e| self.error(format!("Invalid value {} for {}: {}", value, flag, e)))
+    }
+
+    /// 处理 --input、--input-string 和 --output，不是这些选项时返回 false
+    fn io(&mut self, arg: &str) -> Result<bool, UsageError> {
+        let input = match arg {
+            "--input" => Input::File(self.value(arg)?),
+            "--input-string" => Input::String(self.value(arg)?),
+            "--output" => {
+                self.output = Some(self.value(arg)?);
+                return Ok(true);
+            }
+            _ => return Ok(false),
+        };
+        if self.input.is_some() {
+            return Err(self.error("Give only one of --input and --input-string"));
+        }
+        self.input = Some(input);
+        Ok(true)
+    }
+
+    /// 处理选项以外的参数: --help、-e、未知的选项和 PROGRAM。返回 false 时应输出帮助后退出
+    fn other(&mut self, arg: String) -> Result<bool, UsageError> {
+        let program = match arg.as_str() {
+            "-h" | "--help" => return Ok(false),
+            "-e" => Program::Inline(self.value("-e")?),
+            _ if arg.starts_with('-') && arg != "-" => return Err(self.error(format!("Unknown option {}", arg))),
+            _ => Program::Path(arg),
+        };
+        match (&self.program, program) {
+            (None, program) => self.program = Some(program),
+            (Some(Program::Inline(_)), _) | (_, Program::Inline(_)) => return Err(self.error("Give either PROGRAM or -e, not both")),
+            (_, Program::Path(path)) => return Err(self.error(format!("Unexpected argument {}", path))),
+        }
+        Ok(true)
+    }
+
+    /// 读取程序，返回程序的名字和代码
+    fn program(&self) -> Result<(String, Vec<u8>), Box<dyn std::error::Error>> {
+        match &self.program {
+            Some(Program::Path(path)) => Ok((path.clone(), read_program(path)?)),
+            Some(Program::Inline(code)) => Ok(("-e".to_string(), code.clone().into_bytes())),
+            None => Err(self.error("Missing PROGRAM or -e CODE").into()),
+        }
+    }
+
+    /// --input 或 --input-string 指定的输入，都没有时为 None
+    fn input(&self) -> Result<Option<Box<dyn Read + Send>>, Box<dyn std::error::Error>> {
+        match &self.input {
+            Some(Input::File(path)) => {
+                let f = std::fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+                Ok(Some(Box::new(BufReader::new(f))))
+            }
+            Some(Input::String(s)) => Ok(Some(Box::new(std::io::Cursor::new(s.clone().into_bytes())))),
+            None => Ok(None),
+        }
+    }
+
+    /// --output 指定的输出，没有时为 None
+    fn output(&self) -> Result<Option<Box<dyn Write + Send>>, Box<dyn std::error::Error>> {
+        match &self.output {
+            Some(path) => {
+                let f = std::fs::File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+                Ok(Some(Box::new(BufWriter::new(f))))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl Iterator for Args {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.args.next()
+    }
+}
+
+fn default_engine() -> Engine {
+    if Engine::Jit.is_supported() { Engine::Jit } else { Engine::It }
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1);
+    let command = args.next();
+    let args: Vec<String> = args.collect();
+
+    let result = match command.as_deref() {
+        Some("run") => run(Args::new(args, RUN_USAGE)),
+        Some("compile") => compile(Args::new(args, COMPILE_USAGE)),
+        Some("debug") => debug(Args::new(args, DEBUG_USAGE)),
+        Some("fmt") => fmt(Args::new(args, FMT_USAGE)),
+        Some("check") => check(Args::new(args, CHECK_USAGE)),
+        Some("bench") => bench(Args::new(args, BENCH_USAGE)),
+        Some("repl") => repl(Args::new(args, REPL_USAGE)),
+        Some("dap") => dap::serve(BufReader::new(std::io::stdin()), std::io::stdout()),
+        Some("lsp") => lsp::serve(std::io::stdin().lock(), std::io::stdout()),
+        Some("-h" | "--help" | "help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(command) => Err(UsageError { message: format!("Unknown command {}", command), usage: USAGE }.into()),
+        None => Err(UsageError { message: "Missing command".to_string(), usage: USAGE }.into()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => match e.downcast_ref::<UsageError>() {
+            Some(e) => {
+                eprintln!("error: {}\n\n{}", e.message, e.usage);
+                ExitCode::from(2)
+            }
+            None => {
+                eprintln!("error: {}", e);
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
+
+fn run(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let mut engine = default_engine();
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--engine" => engine = args.parse("--engine")?,
+            "--max-steps" => options.limits = options.limits.max_steps(args.parse("--max-steps")?),
+            "--timeout" => {
+                let seconds: f64 = args.parse("--timeout")?;
+                options.limits = options.limits.timeout(Duration::from_secs_f64(seconds));
+            }
+            "--dump" => options.debug = Some(args.parse("--dump")?),
+            _ if args.io(&arg)? => {}
+            _ => {
+                if !args.other(arg)? {
+                    println!("{}", RUN_USAGE);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    let (_, data) = args.program()?;
+    options.input = args.input()?;
+    options.output = args.output()?;
+    engine.run(data, options)
+}
+
+fn compile(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let mut engine = Engine::It;
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--engine" => engine = args.parse("--engine")?,
+            "-o" => output = Some(args.value("-o")?),
+            _ => {
+                if !args.other(arg)? {
+                    println!("{}", COMPILE_USAGE);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    let (_, data) = args.program()?;
+    let mut out = std::io::stdout().lock();
+    match engine {
+        Engine::It => {
+            let source_map = SourceMap::new(&data);
+            let parsed = interpreter_it::parse(&data, false);
+            if let Some(offset) = parsed.unmatched.first() {
+                let (line, column) = source_map.position(*offset);
+                return Err(format!("Unmatched {} at {}:{}", data[*offset] as char, line, column).into());
+            }
+            for (pc, (opcode, span)) in parsed.opcodes.iter().zip(&parsed.spans).enumerate() {
+                let (line, column) = source_map.position(span.start);
+                writeln!(out, "{:>6}  {:<12} {}:{}", pc, format!("{:?}", opcode), line, column)?;
+            }
+        }
+        Engine::Jit => {
+            let code = machine_code(data)?;
+            match output {
+                Some(path) => std::fs::write(path, code)?,
+                None => {
+                    for (i, chunk) in code.chunks(16).enumerate() {
+                        let bytes: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                        writeln!(out, "{:08x}  {}", i * 16, bytes.join(" "))?;
+                    }
+                }
+            }
+        }
+        Engine::Naive => return Err(args.error("The naive engine has nothing to compile, use it or jit").into()),
+        Engine::Tiered => return Err(args.error("The tiered engine only compiles hot loops while running, use it or jit").into()),
+    }
+    Ok(())
+}
+
+#[cfg(any(all(target_os = "linux", target_arch = "x86_64"), target_arch = "aarch64"))]
+fn machine_code(data: Vec<u8>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    brainfuck_toy::Interpreter::default().machine_code(data)
+}
+
+#[cfg(not(any(all(target_os = "linux", target_arch = "x86_64"), target_arch = "aarch64")))]
+fn machine_code(_: Vec<u8>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    Err("The JIT is not supported on this platform".into())
+}
+
+fn debug(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    while let Some(arg) = args.next() {
+        if arg == "--output" {
+            return Err(args.error("Unknown option --output").into());
+        }
+        if !args.io(&arg)? && !args.other(arg)? {
+            println!("{}", DEBUG_USAGE);
+            return Ok(());
+        }
+    }
+
+    let (_, data) = args.program()?;
+    let mut debugger = Debugger::new(data)?;
+    if let Some(mut input) = args.input()? {
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)?;
+        debugger.feed(&data);
+    }
+    debugger.repl(std::io::stdin().lock(), std::io::stdout())
+}
+
+fn repl(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    while let Some(arg) = args.next() {
+        if !args.other(arg)? {
+            println!("{}", REPL_USAGE);
+            return Ok(());
+        }
+    }
+
+    let mut repl = Repl::default();
+    let mut input = std::io::stdin().lock();
+    let mut out = std::io::stdout();
+    if args.program.is_some() {
+        let (_, data) = args.program()?;
+        repl.eval(&data, &mut input, &mut out)?;
+    }
+    repl.run(input, out)
+}
+
+fn fmt(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let mut formatter = Formatter::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--width" => {
+                let width: usize = args.parse("--width")?;
+                if width == 0 {
+                    return Err(args.error("--width must be positive").into());
+                }
+                formatter = formatter.with_width(width);
+            }
+            "--indent" => formatter = formatter.with_indent(args.parse("--indent")?),
+            "--strip-comments" => formatter = formatter.with_comments(false),
+            "--dump" => formatter = formatter.with_debug(true),
+            _ => {
+                if !args.other(arg)? {
+                    println!("{}", FMT_USAGE);
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    let (_, data) = args.program()?;
+    print!("{}", formatter.format(&data)?);
+    Ok(())
+}
+
+fn check(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    while let Some(arg) = args.next() {
+        if !args.other(arg)? {
+            println!("{}", CHECK_USAGE);
+            return Ok(());
+        }
+    }
+
+    let (path, data) = args.program()?;
+    let source_map = SourceMap::new(&data);
+    let parsed = interpreter_it::parse(&data, false);
+    for offset in &parsed.unmatched {
+        let (line, column) = source_map.position(*offset);
+        eprintln!("{}:{}:{}: unmatched {}", path, line, column, data[*offset] as char);
+    }
+    if !parsed.unmatched.is_empty() {
+        return Err(format!("{} unmatched brackets", parsed.unmatched.len()).into());
+    }
+
+    let loops = parsed.opcodes.iter().filter(|opcode| matches!(opcode, ItOpcode::LSB(_))).count();
+    println!("{}: ok, {} instructions, {} loops", path, parsed.opcodes.len(), loops);
+    Ok(())
+}
+
+fn bench(mut args: Args) -> Result<(), Box<dyn std::error::Error>> {
+    let mut engines = Vec::new();
+    let mut runs = DEFAULT_RUNS;
+    let mut json = false;
+    let mut paths = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--engine" => engines.push(args.parse("--engine")?),
+            "--runs" => runs = args.parse("--runs")?,
+            "--json" => json = true,
+            "--output" => return Err(args.error("Unknown option --output").into()),
+            _ if args.io(&arg)? => {}
+            _ if !arg.starts_with('-') => paths.push(arg),
+            _ => {
+                if !args.other(arg)? {
+                    println!("{}", BENCH_USAGE);
+                    return Ok(());
+                }
+            }
+        }
+    }
+    if runs == 0 {
+        return Err(args.error("--runs must be positive").into());
+    }
+    if engines.is_empty() {
+        engines = Engine::all();
+    }
+    if let Some(engine) = engines.iter().find(|engine| !engine.is_supported()) {
+        return Err(format!("The {} engine is not supported on this platform", engine).into());
+    }
+
+    if !paths.is_empty() && matches!(args.program, Some(Program::Inline(_))) {
+        return Err(args.error("Give either PROGRAM or -e, not both").into());
+    }
+
+    let mut input = Vec::new();
+    if let Some(mut reader) = args.input()? {
+        reader.read_to_end(&mut input)?;
+    }
+    let mut programs = Vec::new();
+    for path in paths {
+        programs.push(bench::Program::new(path.clone(), read_program(&path)?, input.clone()));
+    }
+    if args.program.is_some() {
+        let (name, data) = args.program()?;
+        programs.push(bench::Program::new(name, data, input.clone()));
+    }
+    if programs.is_empty() {
+        programs = bench::default_programs();
+    }
+
+    let mut measurements = Vec::new();
+    for program in &programs {
+        measurements.extend(bench::measure(program, &engines, runs)?);
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&bench::to_json(&measurements))?);
+    } else {
+        print!("{}", bench::report(&measurements));
+    }
+    Ok(())
+}
@@ -0,0 +1,10 @@
+use brainfuck_toy::debugger::Debugger;
+use brainfuck_toy::parse_data;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let data = parse_data()?;
+    let mut debugger = Debugger::new(data)?;
+    debugger.repl(std::io::stdin().lock(), std::io::stdout())?;
+
+    Ok(())
+}
@@ -1,9 +1,23 @@
 use brainfuck_toy::interpreter::Interpreter;
-use brainfuck_toy::parse_data;
+use brainfuck_toy::trace::TraceOptions;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let data = parse_data()?;
+    let usage = format!("Usage: interpreter {} PROGRAM", TraceOptions::USAGE);
+    let mut program = None;
+    let mut trace = TraceOptions::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if !trace.parse_arg(&arg, &mut args)? {
+            program = Some(arg);
+        }
+    }
+
+    let data = std::fs::read(program.ok_or(usage)?)?;
     let mut interpreter = Interpreter::default();
+    if let Some(tracer) = trace.tracer()? {
+        interpreter = interpreter.with_trace(tracer);
+    }
     interpreter.run(data)?;
 
     Ok(())
@@ -1,10 +1,95 @@
+use brainfuck_toy::coverage::Coverage;
 use brainfuck_toy::interpreter_it::Interpreter;
-use brainfuck_toy::parse_data;
+use brainfuck_toy::trace::TraceOptions;
+
+use std::io::prelude::*;
+
+const CHECKPOINT_STEPS: u64 = 100_000_000; // 每执行这么多步保存一次快照
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let data = parse_data()?;
+    let usage = format!(
+        "Usage: interpreter_it [--checkpoint SNAPSHOT] [--profile] [--profile-folded FILE] [--coverage] [--lcov FILE] {} (PROGRAM | --restore SNAPSHOT)",
+        TraceOptions::USAGE
+    );
+    let mut program = None;
+    let mut checkpoint = None;
+    let mut restore = None;
+    let mut profile = false;
+    let mut folded = None;
+    let mut coverage = false;
+    let mut lcov = None;
+    let mut trace = TraceOptions::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if trace.parse_arg(&arg, &mut args)? {
+            continue;
+        }
+        match arg.as_str() {
+            "--checkpoint" => checkpoint = Some(args.next().ok_or(usage.as_str())?),
+            "--restore" => restore = Some(args.next().ok_or(usage.as_str())?),
+            "--profile" => profile = true,
+            "--profile-folded" => folded = Some(args.next().ok_or(usage.as_str())?),
+            "--coverage" => coverage = true,
+            "--lcov" => lcov = Some(args.next().ok_or(usage.as_str())?),
+            _ => program = Some(arg),
+        }
+    }
+
+    // 报告按源码的行列号统计，快照中没有源码
+    let profiling = profile || folded.is_some() || coverage || lcov.is_some();
+    if restore.is_some() && profiling {
+        return Err("--profile, --profile-folded, --coverage and --lcov can't be used with --restore".into());
+    }
+
     let mut interpreter = Interpreter::default();
-    interpreter.run(data)?;
+    if let Some(path) = checkpoint {
+        interpreter = interpreter.with_checkpoint(path, CHECKPOINT_STEPS);
+    }
+    if profiling {
+        interpreter = interpreter.with_profile();
+    }
+    if let Some(tracer) = trace.tracer()? {
+        interpreter = interpreter.with_trace(tracer);
+    }
+
+    let mut source = Vec::new();
+    match (restore, program.as_ref()) {
+        // 快照中已经有代码
+        (Some(_), Some(_)) => return Err("PROGRAM can't be used with --restore".into()),
+        (Some(path), None) => {
+            let f = std::fs::File::open(path)?;
+            interpreter.restore(std::io::BufReader::new(f))?;
+            interpreter.resume()?;
+        }
+        (None, Some(path)) => {
+            source = std::fs::read(path)?;
+            interpreter.run(source.clone())?;
+        }
+        (None, None) => return Err(usage.into()),
+    }
+
+    // 报告输出到 stderr，不和程序的输出混在一起
+    if let Some(result) = interpreter.profile() {
+        if profile {
+            result.report(&source, std::io::stderr())?;
+        }
+        if let Some(path) = folded {
+            let mut w = std::io::BufWriter::new(std::fs::File::create(path)?);
+            result.folded(&source, &mut w)?;
+            w.flush()?;
+        }
+
+        let result = Coverage::new(source, &result);
+        if coverage {
+            result.annotate(std::io::stderr())?;
+        }
+        if let Some(path) = lcov {
+            let mut w = std::io::BufWriter::new(std::fs::File::create(path)?);
+            result.lcov(program.as_deref().unwrap_or_default(), &mut w)?;
+            w.flush()?;
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,10 @@
+use brainfuck_toy::tiered::Interpreter;
+use brainfuck_toy::parse_data;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let data = parse_data()?;
+    let mut interpreter = Interpreter::default();
+    interpreter.run(data)?;
+
+    Ok(())
+}
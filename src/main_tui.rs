@@ -0,0 +1,12 @@
+use brainfuck_toy::parse_data;
+use brainfuck_toy::tui::App;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut app = App::new(parse_data()?)?;
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+
+    result
+}
@@ -1,5 +1,7 @@
 use once_cell::sync::Lazy;
 
+use std::ops::Range;
+
 pub const OPCODE_SHL: u8     = b'<'; // <: 指针减一
 pub const OPCODE_SHR: u8     = b'>'; // >: 指针加一
 pub const OPCODE_ADD: u8     = b'+'; // +: 指针指向的字节的值加一
@@ -8,6 +10,7 @@ pub const OPCODE_LSB: u8     = b'['; // [: 如果指针指向的单元值为零
 pub const OPCODE_RSB: u8     = b']'; // ]: 如果指针指向的单元值不为零，向前跳转到对应的 [ 指令的次一指令处
 pub const OPCODE_GETCHAR: u8 = b','; // ,: 输入内容到指针指向的单元（ASCII码）
 pub const OPCODE_PUTCHAR: u8 = b'.'; // .: 输出指针指向的单元内容（ASCII码）
+pub const OPCODE_DEBUG: u8   = b'#'; // #: 调试扩展，默认作为注释。启用后在调试器中暂停，否则输出指针和纸带
 
 pub static OPCODE_DICT: Lazy<Vec<u8>> = Lazy::new(|| vec![
     OPCODE_SHL,
@@ -19,3 +22,51 @@ pub static OPCODE_DICT: Lazy<Vec<u8>> = Lazy::new(|| vec![
     OPCODE_GETCHAR,
     OPCODE_PUTCHAR,
 ]);
+
+/// 是否是需要执行的指令，debug 为 true 时包括 #
+pub fn is_opcode(u: u8, debug: bool) -> bool {
+    OPCODE_DICT.contains(&u) || (debug && u == OPCODE_DEBUG)
+}
+
+/// 源码的一段: 一条指令，或者两条指令之间的其他字节
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Opcode(usize, u8),     // 指令在源码中的偏移和指令
+    Comment(Range<usize>), // 连续的注释字节在源码中的范围
+}
+
+/// 把源码切分为指令和注释，各个 Code::from 和格式化都从这里读取指令
+pub struct Tokens<'a> {
+    data: &'a [u8],
+    offset: usize,
+    debug: bool,
+}
+
+impl Iterator for Tokens<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let start = self.offset;
+        let u = *self.data.get(start)?;
+        if is_opcode(u, self.debug) {
+            self.offset += 1;
+            return Some(Token::Opcode(start, u));
+        }
+        let rest = &self.data[start..];
+        self.offset += rest.iter().position(|u| is_opcode(*u, self.debug)).unwrap_or(rest.len());
+        Some(Token::Comment(start..self.offset))
+    }
+}
+
+/// debug 为 true 时 # 是指令，否则是注释
+pub fn tokenize(data: &[u8], debug: bool) -> Tokens<'_> {
+    Tokens { data, offset: 0, debug }
+}
+
+/// 源码中的指令和它们的偏移，跳过注释
+pub fn opcodes(data: &[u8], debug: bool) -> impl Iterator<Item = (usize, u8)> + '_ {
+    tokenize(data, debug).filter_map(|token| match token {
+        Token::Opcode(i, u) => Some((i, u)),
+        Token::Comment(_) => None,
+    })
+}
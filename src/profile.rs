@@ -0,0 +1,231 @@
+use super::debugger::SourceMap;
+use super::interpreter_it::ItOpcode;
+
+use std::io::prelude::*;
+use std::ops::Range;
+
+const REPORT_LIMIT: usize = 10; // 报告中最多列出多少个循环和行
+
+/// 循环的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopKind {
+    Clear,    // [-] 或 [+]，把当前单元清零
+    Multiply, // 没有 I/O 和内层循环，指针最终不动，当前单元每次加减 1，例如 [->++>+++<<]
+    Scan,     // [>] 或 [<<]，找到值为零的单元
+    General,
+}
+
+impl std::fmt::Display for LoopKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            LoopKind::Clear => "clear",
+            LoopKind::Multiply => "multiply",
+            LoopKind::Scan => "scan",
+            LoopKind::General => "general",
+        };
+        f.pad(name)
+    }
+}
+
+/// 根据循环体分类
+pub fn classify(body: &[ItOpcode]) -> LoopKind {
+    match body {
+        [ItOpcode::ADD(v) | ItOpcode::SUB(v)] if v % 2 == 1 => return LoopKind::Clear,
+        [ItOpcode::SHL(_) | ItOpcode::SHR(_)] => return LoopKind::Scan,
+        _ => {}
+    }
+
+    let mut offset: i64 = 0;
+    let mut delta: u8 = 0; // 每次循环 offset 为 0 的单元的变化
+    for opcode in body {
+        match *opcode {
+            ItOpcode::SHL(v) => offset -= v as i64,
+            ItOpcode::SHR(v) => offset += v as i64,
+            ItOpcode::ADD(v) if offset == 0 => delta = delta.wrapping_add(v),
+            ItOpcode::SUB(v) if offset == 0 => delta = delta.wrapping_sub(v),
+            ItOpcode::ADD(_) | ItOpcode::SUB(_) => {}
+            _ => return LoopKind::General,
+        }
+    }
+    if offset == 0 && (delta == 1 || delta == u8::MAX) {
+        LoopKind::Multiply
+    } else {
+        LoopKind::General
+    }
+}
+
+/// 一个循环的执行统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopProfile {
+    pub lsb: usize,        // [ 的指令下标
+    pub rsb: usize,        // ] 的指令下标
+    pub entries: u64,      // 执行到 [ 的次数
+    pub iterations: u64,   // 循环体执行的次数
+    pub instructions: u64, // 包括内层循环在内执行的指令数
+    pub own: u64,          // 不包括内层循环执行的指令数
+    pub kind: LoopKind,
+}
+
+/// interpreter_it 每条指令的执行次数
+pub struct Profile {
+    opcodes: Vec<ItOpcode>,
+    spans: Vec<Range<usize>>, // 从快照恢复时为空
+    counts: Vec<u64>,
+}
+
+impl Profile {
+    pub fn new(opcodes: Vec<ItOpcode>, spans: Vec<Range<usize>>, counts: Vec<u64>) -> Self {
+        Self { opcodes, spans, counts }
+    }
+
+    pub fn opcodes(&self) -> &[ItOpcode] {
+        &self.opcodes
+    }
+
+    /// 第 pc 条指令在源码中对应的范围
+    pub fn span(&self, pc: usize) -> Option<Range<usize>> {
+        self.spans.get(pc).cloned()
+    }
+
+    /// 每条指令的执行次数
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// 执行的指令总数
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// 所有循环，按指令下标排序
+    pub fn loops(&self) -> Vec<LoopProfile> {
+        let mut loops = Vec::new();
+        let mut stack = Vec::new();
+        for (pc, opcode) in self.opcodes.iter().enumerate() {
+            match opcode {
+                ItOpcode::LSB(_) => stack.push((pc, 0)),
+                ItOpcode::RSB(_) => {
+                    // nested 是直接内层循环执行的指令数
+                    let Some((lsb, nested)) = stack.pop() else {
+                        continue;
+                    };
+                    let instructions: u64 = self.counts[lsb..=pc].iter().sum();
+                    if let Some((_, parent)) = stack.last_mut() {
+                        *parent += instructions;
+                    }
+                    let iterations = if pc > lsb + 1 { self.counts[lsb + 1] } else { 0 };
+                    loops.push(LoopProfile {
+                        lsb,
+                        rsb: pc,
+                        entries: self.counts[lsb],
+                        iterations,
+                        instructions,
+                        own: instructions - nested,
+                        kind: classify(&self.opcodes[lsb + 1..pc]),
+                    });
+                }
+                _ => {}
+            }
+        }
+        loops.sort_by_key(|l| l.lsb);
+        loops
+    }
+
+    /// 按不包括内层循环执行的指令数从多到少排序的循环
+    pub fn hot_loops(&self) -> Vec<LoopProfile> {
+        let mut loops = self.loops();
+        loops.sort_by(|a, b| b.own.cmp(&a.own).then(a.lsb.cmp(&b.lsb)));
+        loops
+    }
+
+    /// 每一行源码执行的指令数，下标为行号减 1
+    pub fn lines(&self, source_map: &SourceMap) -> Vec<u64> {
+        let mut lines = Vec::new();
+        for (span, count) in self.spans.iter().zip(&self.counts) {
+            let (line, _) = source_map.position(span.start);
+            if lines.len() < line {
+                lines.resize(line, 0);
+            }
+            lines[line - 1] += count;
+        }
+        lines
+    }
+
+    /// 循环的名字: [ 在源码中的行列号，没有源码位置时为指令下标
+    fn label(&self, source_map: &SourceMap, lsb: usize) -> String {
+        match self.span(lsb) {
+            Some(span) => {
+                let (line, column) = source_map.position(span.start);
+                format!("{}:{}", line, column)
+            }
+            None => format!("#{}", lsb),
+        }
+    }
+
+    /// 折叠栈格式，可以用 inferno 或 flamegraph.pl 生成火焰图。
+    /// 每层栈是一个循环，最外层是 main，每行的数值是这一层循环自身执行的指令数
+    pub fn folded<W: Write>(&self, source: &[u8], mut out: W) -> std::io::Result<()> {
+        let source_map = SourceMap::new(source);
+        let mut frames = vec![("main".to_string(), 0)];
+        let mut stack = vec![0]; // frames 的下标
+
+        for (pc, (opcode, count)) in self.opcodes.iter().zip(&self.counts).enumerate() {
+            if let ItOpcode::LSB(_) = opcode {
+                let path = format!("{};{}", frames[stack[stack.len() - 1]].0, self.label(&source_map, pc));
+                frames.push((path, 0));
+                stack.push(frames.len() - 1);
+            }
+            frames[stack[stack.len() - 1]].1 += count;
+            if let ItOpcode::RSB(_) = opcode {
+                if stack.len() > 1 {
+                    stack.pop();
+                }
+            }
+        }
+
+        for (path, count) in frames {
+            if count > 0 {
+                writeln!(out, "{} {}", path, count)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 文本报告: 最热的循环和源码行
+    pub fn report<W: Write>(&self, source: &[u8], mut out: W) -> std::io::Result<()> {
+        let source_map = SourceMap::new(source);
+        let total = self.total().max(1);
+        let share = |n: u64| n as f64 * 100.0 / total as f64;
+
+        writeln!(out, "{} instructions executed", self.total())?;
+        writeln!(out)?;
+        writeln!(out, "Hot loops")?;
+        writeln!(out, "rank  location              own          total   share      entries     iterations  per entry  kind")?;
+        for (rank, l) in self.hot_loops().iter().take(REPORT_LIMIT).enumerate() {
+            let location = self.label(&source_map, l.lsb);
+            let per_entry = l.iterations as f64 / l.entries.max(1) as f64;
+            writeln!(
+                out,
+                "{:>4}  {:<10} {:>14} {:>14} {:>6.2}% {:>12} {:>14} {:>10.1}  {}",
+                rank + 1, location, l.own, l.instructions, share(l.own), l.entries, l.iterations, per_entry, l.kind
+            )?;
+        }
+
+        if self.spans.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines: Vec<(usize, u64)> = self.lines(&source_map).into_iter().enumerate().collect();
+        lines.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        writeln!(out)?;
+        writeln!(out, "Hot lines")?;
+        writeln!(out, "  line   instructions   share  source")?;
+        for (line, count) in lines.into_iter().take(REPORT_LIMIT).filter(|(_, count)| *count > 0) {
+            let text = source_map.line(line + 1).map(|range| String::from_utf8_lossy(&source[range]).into_owned());
+            let text: String = text.unwrap_or_default().trim().chars().take(48).collect();
+            writeln!(out, "{:>6} {:>14} {:>6.2}%  {}", line + 1, count, share(count), text)?;
+        }
+
+        Ok(())
+    }
+}
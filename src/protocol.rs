@@ -0,0 +1,76 @@
+use std::io::prelude::*;
+
+use serde_json::Value;
+
+const MAX_CONTENT_LENGTH: usize = 64 << 20; // 消息最大的字节数
+
+/// 消息的头或者内容无效。这条消息被丢弃，之后的消息仍然可以读取
+#[derive(Debug)]
+pub struct InvalidMessage(pub String);
+
+impl std::fmt::Display for InvalidMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid message: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidMessage {}
+
+/// 读取一条带 Content-Length 头的 JSON 消息，DAP 和 LSP 都使用这种格式。输入结束时返回 None，
+/// 消息无效时返回 InvalidMessage，其他错误是读取输入时的 I/O 错误
+pub fn read_message<R: BufRead>(r: &mut R) -> Result<Option<Value>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut length = None;
+    let mut invalid = None;
+    loop {
+        let mut line = Vec::new();
+        if r.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        let Ok(line) = std::str::from_utf8(&line) else {
+            invalid.get_or_insert_with(|| "header is not UTF-8".to_string());
+            continue;
+        };
+        let line = line.trim_end();
+        if line.is_empty() {
+            if length.is_some() || invalid.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                match value.trim().parse::<usize>() {
+                    Ok(value) => length = Some(value),
+                    Err(_) => invalid = Some(format!("Content-Length {:?}", value.trim())),
+                }
+            }
+        }
+    }
+
+    // 不知道内容的长度时无法跳过内容，从下一行开始继续寻找消息头
+    let Some(length) = length else {
+        return Err(Box::new(InvalidMessage(invalid.unwrap_or_else(|| "missing Content-Length".to_string()))));
+    };
+    if length > MAX_CONTENT_LENGTH {
+        std::io::copy(&mut r.take(length as u64), &mut std::io::sink())?;
+        return Err(Box::new(InvalidMessage(format!("Content-Length {} exceeds {}", length, MAX_CONTENT_LENGTH))));
+    }
+
+    let mut body = vec![0; length];
+    r.read_exact(&mut body)?;
+    // 其他的头无效时跳过内容
+    if let Some(invalid) = invalid {
+        return Err(Box::new(InvalidMessage(invalid)));
+    }
+    match serde_json::from_slice(&body) {
+        Ok(message) => Ok(Some(message)),
+        Err(e) => Err(Box::new(InvalidMessage(e.to_string()))),
+    }
+}
+
+/// 写一条带 Content-Length 头的 JSON 消息
+pub fn write_message<W: Write>(w: &mut W, message: &Value) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(w, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    w.flush()
+}
@@ -0,0 +1,174 @@
+use super::debugger::SourceMap;
+use super::format_tape;
+use super::interpreter_it::{parse, Interpreter, State};
+use super::opcode;
+
+use std::io::prelude::*;
+
+const TAPE_RADIUS: usize = 4; // 每段代码执行后显示指针左右多少个单元
+
+const HELP: &str = "Each line of brainfuck runs on the same tape and pointer; a line with an unclosed [ continues on the next one.
+  :tape [RADIUS]  show RADIUS cells around the pointer, or the whole tape
+  :reset          clear the tape, the pointer and the session
+  :load FILE      run FILE on the current tape and add it to the session
+  :save FILE      save the code run in this session to FILE
+  :help           show this help
+  :quit           exit";
+
+/// 交互式执行: 每一行代码都在同一个纸带和指针上执行，执行后显示指针附近的纸带
+#[derive(Default)]
+pub struct Repl {
+    interpreter: Interpreter,
+    session: Vec<u8>, // 执行过的代码，:save 保存
+    pending: Vec<u8>, // 还有没配对的 [ 的代码，等待后面的行
+}
+
+impl Repl {
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    /// 执行过的代码
+    pub fn session(&self) -> &[u8] {
+        &self.session
+    }
+
+    /// 在当前纸带上执行一段代码，需要输入时从 input 读取一行。执行成功的代码加入会话，
+    /// 出错时纸带、指针和输入回到执行前的状态，会话保存的代码仍然可以重现纸带
+    pub fn eval<R: BufRead, W: Write>(&mut self, code: &[u8], input: &mut R, out: &mut W) -> Result<(), Box<dyn std::error::Error>> {
+        self.interpreter.load(code.to_vec())?;
+        let frame = self.interpreter.frame();
+        if let Err(e) = self.execute(input, out) {
+            self.interpreter.set_frame(&frame);
+            return Err(e);
+        }
+
+        self.session.extend_from_slice(code);
+        if !code.ends_with(b"\n") {
+            self.session.push(b'\n');
+        }
+        Ok(())
+    }
+
+    /// 执行加载的代码
+    fn execute<R: BufRead, W: Write>(&mut self, input: &mut R, out: &mut W) -> Result<(), Box<dyn std::error::Error>> {
+        let mut last = b'\n'; // 最后输出的字节，不是换行时补一个换行再显示纸带
+        loop {
+            let state = self.interpreter.run_until_input()?;
+            let output = self.interpreter.take_output();
+            if let Some(c) = output.last() {
+                last = *c;
+            }
+            out.write_all(&output)?;
+
+            match state {
+                State::NeedsInput => {
+                    if last != b'\n' {
+                        writeln!(out)?;
+                        last = b'\n';
+                    }
+                    write!(out, "input> ")?;
+                    out.flush()?;
+                    let mut line = String::new();
+                    if input.read_line(&mut line)? == 0 {
+                        return Err("Unexpected end of input".into());
+                    }
+                    self.interpreter.feed(line.as_bytes());
+                }
+                State::Halted => break,
+                State::Running | State::Debug => {}
+            }
+        }
+        if last != b'\n' {
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// 从 input 读取代码和命令，直到输入结束或 :quit
+    pub fn run<R: BufRead, W: Write>(&mut self, mut input: R, mut out: W) -> Result<(), Box<dyn std::error::Error>> {
+        writeln!(out, "Type brainfuck to run it, :help for commands")?;
+        loop {
+            write!(out, "{}", if self.pending.is_empty() { "bf> " } else { "... " })?;
+            out.flush()?;
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                writeln!(out)?;
+                break;
+            }
+
+            if self.pending.is_empty() && line.trim_start().starts_with(':') {
+                match self.command(line.trim(), &mut input, &mut out) {
+                    Ok(true) => continue,
+                    Ok(false) => break,
+                    Err(e) => {
+                        writeln!(out, "error: {}", e)?;
+                        continue;
+                    }
+                }
+            }
+
+            self.pending.extend_from_slice(line.as_bytes());
+            if !self.pending.iter().any(|c| opcode::is_opcode(*c, false)) {
+                self.pending.clear();
+                continue;
+            }
+            let parsed = parse(&self.pending, false);
+            if let Some(&offset) = parsed.unmatched.iter().find(|i| self.pending[**i] == opcode::OPCODE_RSB) {
+                let (line, column) = SourceMap::new(&self.pending).position(offset);
+                writeln!(out, "error: unmatched ] at {}:{}", line, column)?;
+                self.pending.clear();
+                continue;
+            }
+            if !parsed.unmatched.is_empty() {
+                // 等待后面的行关闭循环
+                continue;
+            }
+
+            let code = std::mem::take(&mut self.pending);
+            match self.eval(&code, &mut input, &mut out) {
+                Ok(()) => writeln!(out, "{}", self.tape_window(TAPE_RADIUS))?,
+                Err(e) => writeln!(out, "error: {}", e)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn tape_window(&self, radius: usize) -> String {
+        format_tape(self.interpreter.tape(), self.interpreter.pointer(), radius)
+    }
+
+    /// 执行 : 开头的命令，返回 false 时退出
+    fn command<R: BufRead, W: Write>(&mut self, line: &str, input: &mut R, out: &mut W) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut words = line.splitn(2, ' ');
+        let command = words.next().unwrap_or("");
+        let arg = words.next().unwrap_or("").trim();
+
+        match command {
+            ":t" | ":tape" => {
+                let radius = if arg.is_empty() { self.interpreter.tape().len() } else { arg.parse()? };
+                writeln!(out, "pointer {}\n{}", self.interpreter.pointer(), self.tape_window(radius))?;
+            }
+            ":reset" => {
+                self.interpreter.reset();
+                self.session.clear();
+                writeln!(out, "Tape and session cleared")?;
+            }
+            ":load" if !arg.is_empty() => {
+                let code = std::fs::read(arg)?;
+                self.eval(&code, input, out)?;
+                writeln!(out, "{}", self.tape_window(TAPE_RADIUS))?;
+            }
+            ":save" if !arg.is_empty() => {
+                std::fs::write(arg, &self.session)?;
+                writeln!(out, "Saved {} bytes to {}", self.session.len(), arg)?;
+            }
+            ":load" | ":save" => writeln!(out, "Usage: {} FILE", command)?,
+            ":h" | ":help" => writeln!(out, "{}", HELP)?,
+            ":q" | ":quit" => return Ok(false),
+            _ => writeln!(out, "Unknown command {:?}, try :help", command)?,
+        }
+        Ok(true)
+    }
+}
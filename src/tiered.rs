@@ -0,0 +1,395 @@
+use std::collections;
+use std::io::prelude::*;
+use std::time::{Duration, Instant};
+
+use dynasmrt::{dynasm, DynasmApi, DynasmLabelApi};
+
+use super::jit::ItOpcode as ItOpcode;
+use super::jit::Code as Code;
+use super::dump_tape;
+use super::io::Io;
+use super::limit::{Guard, Limits};
+
+const HOT_LOOP_THRESHOLD: u32 = 1000; // 循环回跳次数超过该值后编译
+
+/// 编译后的循环退出时的状态
+#[repr(C)]
+struct Exit {
+    ptr: *mut u8, // 退出时的指针
+    pc: usize,    // 回到解释器后继续执行的位置
+}
+
+/// 编译后的循环与解释器共享的计数
+#[repr(C)]
+struct Counter {
+    steps: u64,  // 已执行的指令数 [ctx + 0]
+    budget: u64, // 在循环回跳处，步数达到该值时回到解释器检查执行限制 [ctx + 8]
+    cancel: *const u8, // 取消标志，不为零时回到解释器 [ctx + 16]
+}
+
+/// 编译后的循环: fn(指针, 纸带起始地址, 纸带结束地址, 计数) -> Exit
+type LoopFn = extern "sysv64" fn(*mut u8, *const u8, *const u8, *mut Counter) -> Exit;
+
+/// 编译后的热点循环
+struct CompiledLoop {
+    _buffer: dynasmrt::ExecutableBuffer,
+    fun: LoopFn,
+}
+
+impl CompiledLoop {
+    /// 编译 [lsb, rsb] 之间的循环，从循环体的第一条指令进入。
+    /// 指针移出纸带时跳回解释器，由解释器扩展纸带后继续执行。
+    /// counted 为 false 时不生成计数代码，cancellable 为 false 时不检查取消标志
+    fn compile(it_opcodes: &[ItOpcode], lsb: usize, rsb: usize, counted: bool, cancellable: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut ops = dynasmrt::x64::Assembler::new()?;
+        let mut stack = Vec::new();
+        let mut bails = Vec::new(); // (label, pc)
+        let mut pending: i32 = 0; // 还没有累加到 r15 的指令数
+
+        let entry_point = ops.offset();
+
+        // rbx: 指针, r12: 纸带起始地址, r13: 纸带结束地址, r14: 计数, r15: 已执行的指令数
+        dynasm!(ops
+            ; .arch x64
+            ; push rbx
+            ; push r12
+            ; push r13
+            ; push r14
+            ; push r15
+            ; mov rbx, rdi
+            ; mov r12, rsi
+            ; mov r13, rdx
+            ; mov r14, rcx
+            ; mov r15, QWORD [r14]
+        );
+
+        macro_rules! flush_steps {
+            () => {
+                if counted && pending > 0 {
+                    dynasm!(ops
+                        ; add r15, pending
+                    );
+                    pending = 0;
+                }
+            };
+        }
+
+        for (pc, opcode) in it_opcodes.iter().enumerate().take(rsb + 1).skip(lsb) {
+            if pc > lsb {
+                pending += 1;
+            }
+            match *opcode {
+                ItOpcode::SHL(v) => {
+                    let bail = ops.new_dynamic_label();
+                    bails.push((bail, pc, pending - 1));
+                    dynasm!(ops
+                        ; mov rax, rbx
+                        ; sub rax, v as i32
+                        ; jb => bail
+                        ; cmp rax, r12
+                        ; jb => bail
+                        ; mov rbx, rax
+                    )
+                }
+                ItOpcode::SHR(v) => {
+                    let bail = ops.new_dynamic_label();
+                    bails.push((bail, pc, pending - 1));
+                    dynasm!(ops
+                        ; mov rax, rbx
+                        ; add rax, v as i32
+                        ; jc => bail
+                        ; cmp rax, r13
+                        ; jae => bail
+                        ; mov rbx, rax
+                    )
+                }
+                ItOpcode::ADD(v) => dynasm!(ops
+                    ; add BYTE [rbx], v as i8
+                ),
+                ItOpcode::SUB(v) => dynasm!(ops
+                    ; sub BYTE [rbx], v as i8
+                ),
+                ItOpcode::LSB(_) => {
+                    let l = ops.new_dynamic_label();
+                    let r = ops.new_dynamic_label();
+                    let skip = ops.new_dynamic_label();
+                    stack.push((l, r, skip));
+                    if pc == lsb {
+                        // 解释器在 ] 处回跳时进入，此时单元值一定不为零
+                        dynasm!(ops
+                            ; => l
+                        )
+                    } else {
+                        flush_steps!();
+                        dynasm!(ops
+                            ; cmp BYTE [rbx], 0
+                            ; jz => skip
+                            ; => l
+                        )
+                    }
+                }
+                ItOpcode::RSB(v) => {
+                    let (l, r, skip) = stack.pop().ok_or("Pop from empty stack")?;
+                    flush_steps!();
+                    if counted {
+                        // 循环回跳时步数达到 budget 或被取消，回到解释器检查执行限制
+                        let resume = ops.new_dynamic_label();
+                        bails.push((resume, v as usize + 1, 0));
+                        dynasm!(ops
+                            ; cmp BYTE [rbx], 0
+                            ; jz => r
+                            ; cmp r15, QWORD [r14 + 8]
+                            ; jae => resume
+                        );
+                        if cancellable {
+                            dynasm!(ops
+                                ; mov rax, QWORD [r14 + 16]
+                                ; cmp BYTE [rax], 0
+                                ; jnz => resume
+                            );
+                        }
+                        dynasm!(ops
+                            ; jmp => l
+                            ; => skip
+                            ; add r15, 1
+                            ; => r
+                        )
+                    } else {
+                        dynasm!(ops
+                            ; cmp BYTE [rbx], 0
+                            ; jnz => l
+                            ; => skip
+                            ; => r
+                        )
+                    }
+                }
+                ItOpcode::GETCHAR | ItOpcode::PUTCHAR | ItOpcode::DEBUG => {
+                    return Err("Loop with I/O can not be compiled".into());
+                }
+            }
+        }
+
+        // 循环正常结束，回到 ] 的下一条指令
+        dynasm!(ops
+            ; mov rdx, QWORD (rsb + 1) as i64
+            ; ->exit:
+            ; mov QWORD [r14], r15
+            ; mov rax, rbx
+            ; pop r15
+            ; pop r14
+            ; pop r13
+            ; pop r12
+            ; pop rbx
+            ; ret
+        );
+
+        // 回到解释器，从 pc 处继续执行。
+        // 指针越界时，越界的指令还没有执行，由解释器重新计数
+        for (bail, pc, uncounted) in bails {
+            if counted && uncounted > 0 {
+                dynasm!(ops
+                    ; => bail
+                    ; add r15, uncounted
+                    ; mov rdx, QWORD pc as i64
+                    ; jmp ->exit
+                );
+            } else {
+                dynasm!(ops
+                    ; => bail
+                    ; mov rdx, QWORD pc as i64
+                    ; jmp ->exit
+                );
+            }
+        }
+
+        let buffer = ops.finalize().map_err(|_| "Finalize assembler failed")?;
+        let fun: LoopFn = unsafe { std::mem::transmute(buffer.ptr(entry_point)) };
+
+        Ok(CompiledLoop { _buffer: buffer, fun })
+    }
+}
+
+/// 分层执行引擎: 先用中间表解释执行，统计每个循环的回跳次数，
+/// 把不含 I/O 的热点循环编译成机器码执行
+pub struct Interpreter {
+    stack: Vec<u8>, // 保存解释执行的结果
+    s_pointer: usize, // 指针，多次 run 之间保持不变
+    threshold: u32, // 热点循环阈值
+    limits: Limits, // 执行限制
+    debug: Option<usize>, // 启用 # 调试指令时，输出指针左右多少个单元
+    io: Io,
+    compile_time: Duration, // 上一次 run 解析程序和编译热点循环所用的时间
+    compiled_loops: usize,  // 上一次 run 编译的热点循环数
+}
+
+impl std::default::Default for Interpreter {
+    fn default() -> Self {
+        Self { stack: vec![0; 1], s_pointer: 0, threshold: HOT_LOOP_THRESHOLD, limits: Limits::default(), debug: None, io: Io::default(), compile_time: Duration::ZERO, compiled_loops: 0 }
+    }
+}
+
+impl Interpreter {
+    /// 设置循环回跳多少次后编译
+    pub fn with_threshold(mut self, threshold: u32) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// 设置最大指令数、超时时间和取消句柄，超出时返回 limit::Aborted。
+    /// 指令数与 interpreter_it 的计算方式相同，编译后的循环只在回跳处检查
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// 启用 # 调试指令，执行到 # 时把指针和左右 radius 个单元输出到 stderr。
+    /// 包含 # 的循环不会被编译
+    pub fn with_debug(mut self, radius: usize) -> Self {
+        self.debug = Some(radius);
+        self
+    }
+
+    /// 从 input 读取 , 的输入，默认为 stdin
+    pub fn with_input(mut self, input: impl Read + Send + 'static) -> Self {
+        self.io.input = Some(Box::new(input));
+        self
+    }
+
+    /// 把 . 的输出写到 output，默认为 stdout
+    pub fn with_output(mut self, output: impl Write + Send + 'static) -> Self {
+        self.io.output = Some(Box::new(output));
+        self
+    }
+
+    pub fn tape(&self) -> &[u8] {
+        &self.stack
+    }
+
+    pub fn pointer(&self) -> usize {
+        self.s_pointer
+    }
+
+    /// 上一次 run 解析程序和编译热点循环所用的时间
+    pub fn compile_time(&self) -> Duration {
+        self.compile_time
+    }
+
+    /// 上一次 run 编译成机器码的热点循环数
+    pub fn compiled_loops(&self) -> usize {
+        self.compiled_loops
+    }
+
+    /// 清空纸带，指针回到 0
+    pub fn reset(&mut self) {
+        self.stack = vec![0; 1];
+        self.s_pointer = 0;
+    }
+
+    /// 在上一次 run 结束时的纸带和指针上执行程序
+    pub fn run(&mut self, data: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
+        let start = Instant::now();
+        let code = Code::from(data, self.debug.is_some())?;
+        self.compile_time = start.elapsed();
+        self.compiled_loops = 0;
+        let it_opcodes = code.it_opcodes;
+        let it_opcode_len = it_opcodes.len();
+
+        let mut counters: collections::HashMap<usize, u32> = collections::HashMap::new(); // [ 所在位置 -> 回跳次数
+        let mut compiled: collections::HashMap<usize, Option<CompiledLoop>> = collections::HashMap::new(); // None 表示无法编译
+
+        let mut pc = 0; // 程序计数器
+        let mut steps: u64 = 0; // 已执行的指令数
+        let mut guard = Guard::new(&self.limits);
+        let counted = self.limits.is_limited();
+        let cancellable = self.limits.cancel.is_some();
+        let cancel = self.limits.cancel.as_ref().map_or(std::ptr::null(), |cancel| cancel.as_ptr());
+
+        loop {
+            if pc >= it_opcode_len {
+                break;
+            }
+
+            if steps >= guard.budget {
+                guard.poll(steps, self.s_pointer)?;
+            }
+            steps += 1;
+
+            let opcode = &it_opcodes[pc];
+            match *opcode {
+                ItOpcode::SHL(v) => {
+                    self.s_pointer = self.s_pointer.saturating_sub(v as usize);
+                }
+                ItOpcode::SHR(v) => {
+                    self.s_pointer += v as usize;
+                    if self.s_pointer >= self.stack.len() {
+                        // 多预留一些空间，减少编译后的循环因越界回到解释器的次数
+                        let len = std::cmp::max(self.s_pointer + 1, self.stack.len() * 2);
+                        self.stack.resize(len, 0);
+                    }
+                }
+                ItOpcode::ADD(v) => {
+                    self.stack[self.s_pointer] = self.stack[self.s_pointer].overflowing_add(v).0;
+                }
+                ItOpcode::SUB(v) => {
+                    self.stack[self.s_pointer] = self.stack[self.s_pointer].overflowing_sub(v).0;
+                }
+                ItOpcode::LSB(v) => {
+                    if self.stack[self.s_pointer] == 0 {
+                        pc = v as usize;
+                    }
+                }
+                ItOpcode::RSB(v) => {
+                    if self.stack[self.s_pointer] != 0 {
+                        guard.check_cancel(steps, self.s_pointer)?;
+                        let lsb = v as usize;
+                        let count = counters.entry(lsb).or_insert(0);
+                        *count = count.saturating_add(1);
+
+                        // 步数用完时留在解释器，由下一条指令前的检查中止
+                        if *count >= self.threshold && steps < guard.budget {
+                            let compiled_loop = compiled
+                                .entry(lsb)
+                                .or_insert_with(|| {
+                                    let start = Instant::now();
+                                    let compiled_loop = CompiledLoop::compile(&it_opcodes, lsb, pc, counted, cancellable).ok();
+                                    self.compile_time += start.elapsed();
+                                    self.compiled_loops += compiled_loop.is_some() as usize;
+                                    compiled_loop
+                                });
+
+                            if let Some(compiled_loop) = compiled_loop {
+                                // 从循环体进入机器码，纸带、指针和步数交给编译后的循环
+                                let from = self.stack.as_mut_ptr();
+                                let to = unsafe { from.add(self.stack.len()) };
+                                let mut counter = Counter { steps, budget: guard.budget, cancel };
+                                let exit = (compiled_loop.fun)(unsafe { from.add(self.s_pointer) }, from, to, &mut counter);
+                                self.s_pointer = exit.ptr as usize - from as usize;
+                                steps = counter.steps;
+                                pc = exit.pc;
+                                continue;
+                            }
+                        }
+
+                        pc = lsb;
+                    }
+                }
+                ItOpcode::GETCHAR => {
+                    self.stack[self.s_pointer] = self.io.read_byte()?;
+                }
+                ItOpcode::PUTCHAR => {
+                    self.io.write_all(&[self.stack[self.s_pointer]])?;
+                    guard.record(self.stack[self.s_pointer]);
+                }
+                ItOpcode::DEBUG => {
+                    self.io.flush()?;
+                    dump_tape(&self.stack, self.s_pointer, self.debug.unwrap_or_default());
+                }
+            }
+
+            pc += 1;
+        }
+
+        self.io.flush()?;
+        Ok(())
+    }
+}
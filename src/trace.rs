@@ -0,0 +1,184 @@
+use std::io::prelude::*;
+use std::ops::Range;
+use std::path::Path;
+
+const TRACE_MAGIC: &[u8; 7] = b"BFTRACE";
+const TRACE_VERSION: u16 = 2;
+
+/// 执行记录的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,   // 每行一个 JSON 对象
+    Binary, // 文件头之后每条记录 34 字节，小端序
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "binary" => Ok(Format::Binary),
+            _ => Err(format!("Unknown trace format {:?}, expected json or binary", s)),
+        }
+    }
+}
+
+/// 一条执行记录
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    pub step: u64,             // 执行这条指令之前已执行的指令数
+    pub pc: usize,
+    pub offset: Option<usize>, // 指令在源码中的偏移，从快照恢复时没有
+    pub pointer: usize,        // 执行前的指针
+    pub before: u8,            // 执行前指针指向的单元的值
+    pub after: u8,             // 执行后同一个单元的值
+}
+
+/// 把执行的每条指令写到文件，可以只记录源码中的一段，也可以每隔几条记录一条。
+///
+/// 二进制格式: "BFTRACE"、版本号 u16，之后每条记录依次是
+/// step u64、pc u64、offset u64 (没有时为 u64::MAX)、pointer u64、before u8、after u8
+pub struct Tracer {
+    out: Box<dyn Write + Send>,
+    format: Format,
+    range: Option<Range<usize>>, // 只记录源码偏移在这个范围内的指令
+    every: u64,                  // 每隔多少条符合条件的指令记录一条
+    skip: u64,                   // 下一条记录之前还要跳过多少条符合条件的指令
+    started: bool,               // 是否已经写了文件头
+}
+
+impl Tracer {
+    pub fn new<W: Write + Send + 'static>(out: W, format: Format) -> Self {
+        Self {
+            out: Box::new(std::io::BufWriter::new(out)),
+            format,
+            range: None,
+            every: 1,
+            skip: 0,
+            started: false,
+        }
+    }
+
+    pub fn create(path: impl AsRef<Path>, format: Format) -> std::io::Result<Self> {
+        Ok(Self::new(std::fs::File::create(path)?, format))
+    }
+
+    /// 只记录源码偏移在 range 内的指令
+    pub fn with_range(mut self, range: Range<usize>) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// 每 every 条符合条件的指令记录一条
+    pub fn with_sampling(mut self, every: u64) -> Self {
+        self.every = every.max(1);
+        self
+    }
+
+    pub fn record(&mut self, event: &Event) -> std::io::Result<()> {
+        if let Some(range) = &self.range {
+            if !event.offset.is_some_and(|offset| range.contains(&offset)) {
+                return Ok(());
+            }
+        }
+        if self.skip > 0 {
+            self.skip -= 1;
+            return Ok(());
+        }
+        self.skip = self.every - 1;
+
+        match self.format {
+            Format::Json => {
+                let offset = event.offset.map_or("null".to_string(), |offset| offset.to_string());
+                writeln!(
+                    self.out,
+                    r#"{{"step":{},"pc":{},"offset":{},"pointer":{},"before":{},"after":{}}}"#,
+                    event.step, event.pc, offset, event.pointer, event.before, event.after
+                )
+            }
+            Format::Binary => {
+                self.start()?;
+                let offset = event.offset.map_or(u64::MAX, |offset| offset as u64);
+                self.out.write_all(&event.step.to_le_bytes())?;
+                self.out.write_all(&(event.pc as u64).to_le_bytes())?;
+                self.out.write_all(&offset.to_le_bytes())?;
+                self.out.write_all(&(event.pointer as u64).to_le_bytes())?;
+                self.out.write_all(&[event.before, event.after])
+            }
+        }
+    }
+
+    /// 把缓冲的记录写到文件
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        if self.format == Format::Binary {
+            self.start()?;
+        }
+        self.out.flush()
+    }
+
+    /// 写二进制格式的文件头
+    fn start(&mut self) -> std::io::Result<()> {
+        if !self.started {
+            self.out.write_all(TRACE_MAGIC)?;
+            self.out.write_all(&TRACE_VERSION.to_le_bytes())?;
+            self.started = true;
+        }
+        Ok(())
+    }
+}
+
+/// 解析 FROM..TO 形式的源码范围
+pub fn parse_range(s: &str) -> Option<Range<usize>> {
+    let (from, to) = s.split_once("..")?;
+    Some(from.parse().ok()?..to.parse().ok()?)
+}
+
+/// 命令行中的执行记录选项:
+/// --trace FILE [--trace-format json|binary] [--trace-range FROM..TO] [--trace-every N]
+#[derive(Debug, Default)]
+pub struct TraceOptions {
+    path: Option<String>,
+    format: Option<Format>,
+    range: Option<Range<usize>>,
+    every: Option<u64>,
+}
+
+impl TraceOptions {
+    pub const USAGE: &'static str = "[--trace FILE [--trace-format json|binary] [--trace-range FROM..TO] [--trace-every N]]";
+
+    /// arg 是执行记录选项时从 args 读取它的参数并返回 true
+    pub fn parse_arg(
+        &mut self,
+        arg: &str,
+        args: &mut impl Iterator<Item = String>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut value = || args.next().ok_or_else(|| format!("Missing value for {}", arg));
+        match arg {
+            "--trace" => self.path = Some(value()?),
+            "--trace-format" => self.format = Some(value()?.parse()?),
+            "--trace-range" => {
+                let range = value()?;
+                self.range = Some(parse_range(&range).ok_or_else(|| format!("Invalid range {:?}, expected FROM..TO", range))?);
+            }
+            "--trace-every" => self.every = Some(value()?.parse()?),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// 没有指定 --trace 时返回 None
+    pub fn tracer(&self) -> Result<Option<Tracer>, Box<dyn std::error::Error>> {
+        let Some(path) = &self.path else {
+            return Ok(None);
+        };
+        let mut tracer = Tracer::create(path, self.format.unwrap_or(Format::Json))?;
+        if let Some(range) = &self.range {
+            tracer = tracer.with_range(range.clone());
+        }
+        if let Some(every) = self.every {
+            tracer = tracer.with_sampling(every);
+        }
+        Ok(Some(tracer))
+    }
+}
@@ -0,0 +1,293 @@
+use super::debugger::SourceMap;
+use super::interpreter_it::{Interpreter, State};
+
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+const TICK: Duration = Duration::from_millis(50); // 播放时每隔多久执行一批指令
+pub const MAX_SPEED: u64 = 1 << 24; // 每批最多执行的指令数
+const CELL_WIDTH: u16 = 5; // 纸带每个单元显示的宽度
+
+/// 纸带单元的显示方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Radix {
+    Dec,
+    Hex,
+    Ascii,
+}
+
+impl Radix {
+    fn next(self) -> Self {
+        match self {
+            Radix::Dec => Radix::Hex,
+            Radix::Hex => Radix::Ascii,
+            Radix::Ascii => Radix::Dec,
+        }
+    }
+
+    fn format(self, c: u8) -> String {
+        match self {
+            Radix::Dec => c.to_string(),
+            Radix::Hex => format!("{:02x}", c),
+            Radix::Ascii if c.is_ascii_graphic() => (c as char).to_string(),
+            Radix::Ascii if c == b' ' => "' '".to_string(),
+            Radix::Ascii => format!("\\x{:02x}", c),
+        }
+    }
+}
+
+/// 基于 interpreter_it 的终端界面: 源码、纸带、输出和输入队列，可以播放、暂停、单步和调整速度
+pub struct App {
+    interpreter: Interpreter,
+    source: Vec<u8>,
+    source_map: SourceMap,
+    output: Vec<u8>,
+    playing: bool,
+    speed: u64,          // 播放时每批执行的指令数
+    radix: Radix,
+    editing: Option<String>, // 正在输入的内容
+    status: String,
+    halted: bool,
+}
+
+impl App {
+    pub fn new(source: Vec<u8>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut interpreter = Interpreter::default().with_debug(0);
+        interpreter.load(source.clone())?;
+        let source_map = SourceMap::new(&source);
+
+        Ok(Self {
+            interpreter,
+            source,
+            source_map,
+            output: Vec::new(),
+            playing: false,
+            speed: 1,
+            radix: Radix::Dec,
+            editing: None,
+            status: "Paused".to_string(),
+            halted: false,
+        })
+    }
+
+    /// 运行界面，按 q 退出
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if event::poll(TICK)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press && !self.handle_key(key.code)? {
+                        return Ok(());
+                    }
+                }
+            }
+
+            self.tick()?;
+        }
+    }
+
+    /// 播放时执行一批指令，每个 TICK 调用一次
+    pub fn tick(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.playing {
+            self.execute(self.speed)?;
+        }
+        Ok(())
+    }
+
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn speed(&self) -> u64 {
+        self.speed
+    }
+
+    /// 状态栏开头显示的状态
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    /// 处理按键，返回 false 时退出
+    pub fn handle_key(&mut self, code: KeyCode) -> Result<bool, Box<dyn std::error::Error>> {
+        if let Some(text) = &mut self.editing {
+            match code {
+                KeyCode::Enter => {
+                    text.push('\n');
+                    self.interpreter.feed(text.as_bytes());
+                    self.editing = None;
+                    self.status = "Paused".to_string();
+                }
+                KeyCode::Esc => self.editing = None,
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Char(c) => text.push(c),
+                _ => {}
+            }
+            return Ok(true);
+        }
+
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char(' ') => {
+                self.playing = !self.playing && !self.halted;
+                if !self.playing {
+                    self.status = "Paused".to_string();
+                }
+            }
+            KeyCode::Char('s') | KeyCode::Right => {
+                self.playing = false;
+                self.execute(1)?;
+            }
+            KeyCode::Char('+') | KeyCode::Up => self.speed = (self.speed * 2).min(MAX_SPEED),
+            KeyCode::Char('-') | KeyCode::Down => self.speed = (self.speed / 2).max(1),
+            KeyCode::Char('m') => self.radix = self.radix.next(),
+            KeyCode::Char('i') => {
+                self.playing = false;
+                self.editing = Some(String::new());
+            }
+            KeyCode::Char('r') => {
+                let source = self.source.clone();
+                *self = Self { radix: self.radix, speed: self.speed, ..Self::new(source)? };
+            }
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    /// 最多执行 steps 条指令，需要输入、遇到 # 或者结束时暂停
+    fn execute(&mut self, steps: u64) -> Result<(), Box<dyn std::error::Error>> {
+        if self.halted {
+            return Ok(());
+        }
+        let state = self.interpreter.run_for(steps)?;
+        self.output.extend(self.interpreter.take_output());
+
+        match state {
+            State::Running => {
+                self.status = if self.playing { "Playing".to_string() } else { "Paused".to_string() };
+            }
+            State::NeedsInput => {
+                self.playing = false;
+                self.status = "Waiting for input, press i".to_string();
+            }
+            State::Debug => {
+                self.playing = false;
+                self.status = "Paused at #".to_string();
+            }
+            State::Halted => {
+                self.playing = false;
+                self.halted = true;
+                self.status = "Halted".to_string();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        let [source, tape, bottom, help] = Layout::vertical([
+            Constraint::Min(5),
+            Constraint::Length(5),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [output, input] = Layout::horizontal([Constraint::Percentage(70), Constraint::Percentage(30)]).areas(bottom);
+
+        self.draw_source(frame, source);
+        self.draw_tape(frame, tape);
+
+        let text = String::from_utf8_lossy(&self.output);
+        // 只显示最后几行输出
+        let lines: Vec<&str> = text.lines().collect();
+        let shown = lines[lines.len().saturating_sub(output.height.saturating_sub(2) as usize)..].join("\n");
+        frame.render_widget(Paragraph::new(shown).block(Block::bordered().title(" Output ")), output);
+
+        let queue: String = self.interpreter.input().iter().map(|c| Radix::Ascii.format(*c)).collect();
+        let input_text = match &self.editing {
+            Some(text) => format!("{}\n> {}_", queue, text),
+            None => queue,
+        };
+        frame.render_widget(
+            Paragraph::new(input_text).wrap(Wrap { trim: false }).block(Block::bordered().title(" Input ")),
+            input,
+        );
+
+        let help_text = format!(
+            " {} | step {} | speed {}/tick | space play/pause  s step  +/- speed  m {:?}  i input  r restart  q quit",
+            self.status,
+            self.interpreter.steps(),
+            self.speed,
+            self.radix
+        );
+        frame.render_widget(Paragraph::new(help_text).style(Style::default().add_modifier(Modifier::REVERSED)), help);
+    }
+
+    /// 源码，当前指令高亮，滚动到当前指令所在的行
+    fn draw_source(&self, frame: &mut Frame, area: Rect) {
+        let current = self.interpreter.span(self.interpreter.pc()).unwrap_or(self.source.len()..self.source.len());
+        let (current_line, _) = self.source_map.position(current.start.min(self.source.len().saturating_sub(1)));
+        let height = area.height.saturating_sub(2) as usize;
+        let first = current_line.saturating_sub(height / 2).max(1);
+
+        let highlight = Style::default().fg(Color::Black).bg(Color::Yellow);
+        let mut lines = Vec::new();
+        for line in first..first + height {
+            let Some(range) = self.source_map.line(line) else {
+                break;
+            };
+            let mut spans = Vec::new();
+            let (start, end) = (current.start.clamp(range.start, range.end), current.end.clamp(range.start, range.end));
+            spans.push(Span::raw(String::from_utf8_lossy(&self.source[range.start..start]).into_owned()));
+            spans.push(Span::styled(String::from_utf8_lossy(&self.source[start..end]).into_owned(), highlight));
+            spans.push(Span::raw(String::from_utf8_lossy(&self.source[end..range.end]).into_owned()));
+            lines.push(Line::from(spans));
+        }
+
+        let title = format!(" Source  #{} ", self.interpreter.pc());
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title)), area);
+    }
+
+    /// 纸带，指针所在的单元高亮，指针始终在可见范围内
+    fn draw_tape(&self, frame: &mut Frame, area: Rect) {
+        let tape = self.interpreter.tape();
+        let pointer = self.interpreter.pointer();
+        let cells = (area.width.saturating_sub(2) / CELL_WIDTH).max(1) as usize;
+        let first = pointer.saturating_sub(cells / 2);
+
+        let mut index = Vec::new();
+        let mut value = Vec::new();
+        let mut marker = Vec::new();
+        for i in first..first + cells {
+            let c = tape.get(i).copied().unwrap_or(0);
+            let style = if i == pointer {
+                Style::default().fg(Color::Black).bg(Color::Cyan)
+            } else {
+                Style::default()
+            };
+            let width = CELL_WIDTH as usize;
+            index.push(Span::styled(format!("{:>width$}", i), Style::default().fg(Color::DarkGray)));
+            value.push(Span::styled(format!("{:>width$}", self.radix.format(c)), style));
+            marker.push(Span::raw(format!("{:>width$}", if i == pointer { "^" } else { "" })));
+        }
+
+        let title = format!(" Tape  pointer {} ", pointer);
+        let lines = vec![Line::from(index), Line::from(value), Line::from(marker)];
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title)), area);
+    }
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "tokio")]
+
+use brainfuck_toy::interpreter_it::Interpreter;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use std::time::Duration;
+
+const ASYNC_SLICE: u64 = 1 << 14; // run_async 每执行这么多步让出一次
+
+#[tokio::test]
+async fn run_async_over_duplex() {
+    let data = std::fs::read("bf/to_upper.bf").unwrap();
+    let (mut client, server) = tokio::io::duplex(64);
+    let (mut reader, mut writer) = tokio::io::split(server);
+
+    let session = tokio::spawn(async move {
+        let mut interpreter = Interpreter::default();
+        interpreter.run_async(data, &mut reader, &mut writer).await
+    });
+
+    client.write_all(b"hello\n").await.unwrap();
+    let mut output = [0; 5];
+    client.read_exact(&mut output).await.unwrap();
+    assert_eq!(&output, b"HELLO");
+
+    session.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn infinite_loops_yield_every_slice() {
+    // 不让出时 timeout 没有机会触发，测试会一直执行下去
+    let mut interpreter = Interpreter::default();
+    let (mut input, mut output) = (tokio::io::empty(), tokio::io::sink());
+    let run = interpreter.run_async(b"+[]".to_vec(), &mut input, &mut output);
+    assert!(tokio::time::timeout(Duration::from_millis(50), run).await.is_err());
+    // 每次让出都在执行完整的一片之后
+    let steps = interpreter.steps();
+    assert!(steps > 0 && steps % ASYNC_SLICE == 0, "{}", steps);
+}
+
+#[tokio::test]
+async fn sessions_share_a_current_thread_runtime() {
+    let hello = tokio::spawn(async {
+        let mut output = Vec::new();
+        let mut interpreter = Interpreter::default();
+        let data = std::fs::read("bf/hello_world.bf").unwrap();
+        interpreter.run_async(data, &mut tokio::io::empty(), &mut output).await.unwrap();
+        output
+    });
+
+    let mut spinning = Interpreter::default();
+    let (mut input, mut output) = (tokio::io::empty(), tokio::io::sink());
+    let run = spinning.run_async(b"+[]".to_vec(), &mut input, &mut output);
+    assert!(tokio::time::timeout(Duration::from_millis(200), run).await.is_err());
+    // 死循环执行期间另一个程序已经执行完
+    assert!(hello.is_finished());
+    assert_eq!(hello.await.unwrap(), b"Hello World!\n");
+}
+
+#[tokio::test]
+async fn debug_instructions_do_not_stop_the_program() {
+    let mut output = Vec::new();
+    let mut interpreter = Interpreter::default().with_debug(1);
+    interpreter.run_async(b"+#+#.".to_vec(), &mut tokio::io::empty(), &mut output).await.unwrap();
+    assert_eq!(output, [2]);
+}
+
+#[tokio::test]
+async fn input_ends_with_an_error() {
+    let mut interpreter = Interpreter::default();
+    let e = interpreter.run_async(b",.,.".to_vec(), &mut &b"a"[..], &mut tokio::io::sink()).await.unwrap_err();
+    assert_eq!(e.to_string(), "Unexpected end of input");
+}
@@ -0,0 +1,43 @@
+use brainfuck_toy::bench::{measure, report, to_json, Program};
+use brainfuck_toy::engine::Engine;
+
+#[test]
+fn measures_each_engine() {
+    let program = Program::new("-e", b"++++[>+++<-]>,.".to_vec(), b"x".to_vec());
+    let e = measure(&program, &[Engine::Naive], 0).unwrap_err();
+    assert_eq!(e.to_string(), "runs must be positive");
+
+    let measurements = measure(&program, &[Engine::Naive, Engine::It], 2).unwrap();
+    assert_eq!(measurements.len(), 2);
+    assert_eq!(measurements[0].engine, Engine::Naive);
+    assert_eq!(measurements[1].engine, Engine::It);
+    for m in &measurements {
+        assert_eq!((m.program.as_str(), m.runs), ("-e", 2));
+        // ADD(4) [ SHR ADD(3) SHL SUB(1) ] 四次，SHR , .
+        assert_eq!(m.instructions, 2 + 4 * 5 + 3);
+        assert!(m.mean > std::time::Duration::ZERO);
+    }
+    // 速度比以第一个引擎为基准
+    assert_eq!(measurements[0].speedup, 1.0);
+    let expected = measurements[0].mean.as_secs_f64() / measurements[1].mean.as_secs_f64();
+    assert!((measurements[1].speedup - expected).abs() < 1e-9);
+
+    let table = report(&measurements);
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0].split_whitespace().collect::<Vec<_>>(), ["program", "engine", "compile", "execute", "mean", "instructions", "speedup"]);
+    assert!(lines[1].starts_with("-e       naive "), "{}", table);
+    assert!(lines[1].ends_with("  1.00x"), "{}", table);
+    assert!(lines[2].starts_with("-e       it "), "{}", table);
+
+    let json = to_json(&measurements);
+    assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+    let results = json["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    let mut keys: Vec<&str> = results[0].as_object().unwrap().keys().map(|k| k.as_str()).collect();
+    keys.sort();
+    assert_eq!(keys, ["compile_ns", "engine", "execute_ns", "instructions", "mean_ns", "program", "runs", "speedup"]);
+    assert_eq!(results[1]["engine"], "it");
+    assert_eq!(results[1]["instructions"], 25);
+    assert_eq!(results[0]["mean_ns"], measurements[0].mean.as_nanos() as u64);
+}
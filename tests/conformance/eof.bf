@@ -0,0 +1,5 @@
+读到零字节为止，原样输出
+,[.,]
+
+输入已经读完，再读一次时程序以错误结束
+,.
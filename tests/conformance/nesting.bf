@@ -0,0 +1,210 @@
+当前单元是零，跳过一千层嵌套的循环
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+[[[[[[[[[[[[[[[[[[[[
+-.
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+]]]]]]]]]]]]]]]]]]]]
+
+逐层进入一千层嵌套的循环，每层右移一格并置一
++
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+[>+
+
+在最里层输出 ok
+>++++++++++[>+++++++++++<-]>+.----.[-]<++++++++++.[-]<
+
+逐层退出，每层左移一格并清零
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
+<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]<-]
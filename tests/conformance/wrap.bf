@@ -0,0 +1,15 @@
+单元是八位无符号数，加减在两个方向上都会回绕
+
+零减一得到 255，再减去 190 得到 A
+-
+>++++++++++[<------------------->-]<.
+
+加上 257 回绕到 B
+>++++++++++++++++[<++++++++++++++++>-]<+.
+
+从 2 开始每次加 2，回绕到零时循环结束，一共 127 次，减去 60 得到 C
+>++[>+<++]>
+>++++++[<---------->-]<.
+
+换行
+[-]++++++++++.
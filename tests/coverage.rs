@@ -0,0 +1,68 @@
+use brainfuck_toy::coverage::Coverage;
+use brainfuck_toy::interpreter_it::Interpreter;
+
+const SOURCE: &[u8] = b"++ set\n[>+<-] move\n>[-\n]\n<[\n+\n]\ndone\n";
+
+fn coverage(source: &[u8]) -> Coverage {
+    let mut interpreter = Interpreter::default().with_profile().with_output(std::io::sink());
+    interpreter.run(source.to_vec()).unwrap();
+    Coverage::new(source.to_vec(), &interpreter.profile().unwrap())
+}
+
+#[test]
+fn counts_hits_per_byte_and_line() {
+    let coverage = coverage(SOURCE);
+    assert_eq!(&coverage.hits()[..4], [Some(1), Some(1), None, None]);
+    let lines: Vec<Option<u64>> = (1..=8).map(|line| coverage.line_hits(line)).collect();
+    assert_eq!(lines, [Some(1), Some(2), Some(2), Some(2), Some(1), Some(0), Some(1), None]);
+}
+
+#[test]
+fn writes_lcov_records() {
+    let mut out = Vec::new();
+    coverage(SOURCE).lcov("test.bf", &mut out).unwrap();
+    let expected = "\
+TN:
+SF:test.bf
+FN:2,loop@2:1
+FN:3,loop@3:2
+FN:5,loop@5:2
+FNDA:2,loop@2:1
+FNDA:2,loop@3:2
+FNDA:0,loop@5:2
+FNF:3
+FNH:2
+DA:1,1
+DA:2,2
+DA:3,2
+DA:4,2
+DA:5,1
+DA:6,0
+DA:7,1
+LF:7
+LH:6
+end_of_record
+";
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
+
+#[test]
+fn annotates_the_source() {
+    let mut out = Vec::new();
+    coverage(SOURCE).annotate(&mut out).unwrap();
+    let expected = "
+           1 | ++ set
+           2 | [>+<-] move
+           2 | >[-
+           2 | ]
+           1 | <[
+           0 | +
+             | ^
+           1 | ]
+           - | done
+
+15 of 16 instructions executed (93.8%)
+Loops never entered: 5:2
+";
+    assert_eq!(String::from_utf8(out).unwrap(), &expected[1..]);
+}
@@ -0,0 +1,269 @@
+use brainfuck_toy::dap;
+use brainfuck_toy::protocol::{read_message, write_message};
+
+use std::io::{BufReader, Read, Write};
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use serde_json::{json, Value};
+
+/// 用 channel 连接测试和 DAP 服务的字节流
+struct Pipe {
+    receiver: Receiver<Vec<u8>>,
+    buffer: Vec<u8>,
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.buffer.is_empty() {
+            match self.receiver.recv() {
+                Ok(data) => self.buffer = data,
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.buffer.len());
+        buf[..n].copy_from_slice(&self.buffer[..n]);
+        self.buffer.drain(..n);
+        Ok(n)
+    }
+}
+
+struct PipeWriter(Sender<Vec<u8>>);
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.send(buf.to_vec()).map_err(|_| std::io::ErrorKind::BrokenPipe)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+fn pipe() -> (PipeWriter, BufReader<Pipe>) {
+    let (sender, receiver) = channel();
+    (PipeWriter(sender), BufReader::new(Pipe { receiver, buffer: Vec::new() }))
+}
+
+struct Client {
+    writer: PipeWriter,
+    reader: BufReader<Pipe>,
+    seq: u64,
+    events: Vec<Value>,
+}
+
+impl Client {
+    fn start() -> Self {
+        let (writer, server_input) = pipe();
+        let (server_output, reader) = pipe();
+        std::thread::spawn(move || dap::serve(server_input, server_output).unwrap());
+        Self { writer, reader, seq: 0, events: Vec::new() }
+    }
+
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        self.seq += 1;
+        let message = json!({ "seq": self.seq, "type": "request", "command": command, "arguments": arguments });
+        write_message(&mut self.writer, &message).unwrap();
+        loop {
+            let message = read_message(&mut self.reader).unwrap().expect("server closed");
+            if message["type"] == "response" && message["request_seq"] == self.seq {
+                assert_eq!(message["command"], command);
+                return message;
+            }
+            self.events.push(message);
+        }
+    }
+
+    /// 等待事件，返回它之前收到的所有事件中这个事件的 body
+    fn event(&mut self, event: &str) -> Value {
+        if let Some(i) = self.events.iter().position(|m| m["event"] == event) {
+            return self.events.remove(i)["body"].clone();
+        }
+        loop {
+            let message = read_message(&mut self.reader).unwrap().expect("server closed");
+            if message["event"] == event {
+                return message["body"].clone();
+            }
+            self.events.push(message);
+        }
+    }
+
+    /// 收到的程序输出
+    fn output(&mut self) -> String {
+        let mut output = String::new();
+        self.events.retain(|m| {
+            if m["event"] == "output" && m["body"]["category"] == "stdout" {
+                output.push_str(m["body"]["output"].as_str().unwrap());
+                false
+            } else {
+                true
+            }
+        });
+        output
+    }
+}
+
+fn program(name: &str, source: &str) -> String {
+    let path = std::env::temp_dir().join(format!("bf_dap_{}_{}.bf", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn breakpoints_stepping_and_variables() {
+    let path = program("session", "++++++++[>++++++++<-]>+.\n,[.,]\n");
+    let mut client = Client::start();
+
+    let response = client.request("initialize", json!({ "adapterID": "bf", "linesStartAt1": true }));
+    assert_eq!(response["success"], true);
+    assert_eq!(response["body"]["supportsStepBack"], true);
+
+    let response = client.request("launch", json!({ "program": path, "stopOnEntry": true, "input": "hi" }));
+    assert_eq!(response["success"], true);
+    client.event("initialized");
+
+    let response = client.request("setBreakpoints", json!({ "source": { "path": path }, "breakpoints": [{ "line": 2 }, { "line": 9 }] }));
+    let breakpoints = response["body"]["breakpoints"].as_array().unwrap();
+    assert_eq!(breakpoints[0]["verified"], true);
+    assert_eq!(breakpoints[1]["verified"], false);
+
+    client.request("configurationDone", json!({}));
+    assert_eq!(client.event("stopped")["reason"], "entry");
+
+    let threads = client.request("threads", json!({}));
+    assert_eq!(threads["body"]["threads"][0]["id"], 1);
+
+    // 单步进入循环，循环作为调用栈
+    client.request("next", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    client.request("stepIn", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    client.request("stepIn", json!({ "threadId": 1 }));
+    client.event("stopped");
+    let frames = client.request("stackTrace", json!({ "threadId": 1 }))["body"]["stackFrames"].clone();
+    assert_eq!(frames.as_array().unwrap().len(), 2);
+    assert_eq!(frames[0]["line"], 1);
+    assert_eq!(frames[0]["column"], 11);
+    assert_eq!(frames[1]["name"], "loop 1:9");
+
+    // 跳出循环
+    client.request("stepOut", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    let frames = client.request("stackTrace", json!({ "threadId": 1 }))["body"]["stackFrames"].clone();
+    assert_eq!(frames.as_array().unwrap().len(), 1);
+    assert_eq!(frames[0]["column"], 22);
+
+    // 继续执行到第二行的断点
+    client.request("continue", json!({ "threadId": 1 }));
+    let stopped = client.event("stopped");
+    assert_eq!(stopped["reason"], "breakpoint");
+    assert_eq!(stopped["hitBreakpointIds"][0], breakpoints[0]["id"]);
+    assert_eq!(client.output(), "A");
+
+    let scopes = client.request("scopes", json!({ "frameId": 0 }))["body"]["scopes"].clone();
+    assert_eq!(scopes[0]["name"], "Tape");
+    let reference = scopes[0]["variablesReference"].clone();
+    let variables = client.request("variables", json!({ "variablesReference": reference, "start": 0, "count": 2 }));
+    let variables = variables["body"]["variables"].as_array().unwrap().clone();
+    assert_eq!(variables.len(), 2);
+    assert_eq!(variables[1]["name"], "1 (pointer)");
+    assert_eq!(variables[1]["value"], "65 0x41 'A'");
+
+    // 反向执行一步回到 . 之前
+    client.request("stepBack", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    let frames = client.request("stackTrace", json!({ "threadId": 1 }))["body"]["stackFrames"].clone();
+    assert_eq!(frames[0]["line"], 1);
+    assert_eq!(frames[0]["column"], 24);
+
+    // 重新执行 . 之后再次停在断点
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "breakpoint");
+    assert_eq!(client.output(), "A");
+
+    // 输入用完后等待调试控制台的输入
+    client.request("continue", json!({ "threadId": 1 }));
+    let stopped = client.event("stopped");
+    assert_eq!(stopped["description"], "Waiting for input");
+    assert_eq!(client.output(), "hi");
+
+    let response = client.request("evaluate", json!({ "expression": "!" }));
+    assert_eq!(response["body"]["result"], "fed 1 bytes");
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["description"], "Waiting for input");
+    assert_eq!(client.output(), "!");
+
+    client.request("disconnect", json!({}));
+}
+
+#[test]
+fn runs_to_completion_and_pauses() {
+    let path = program("exit", "+[>+++++++++++++++++++++++++++++++++.<-]\n");
+    let mut client = Client::start();
+    client.request("initialize", json!({}));
+    client.request("launch", json!({ "program": path }));
+    client.request("configurationDone", json!({}));
+    assert_eq!(client.event("exited")["exitCode"], 0);
+    client.event("terminated");
+    assert_eq!(client.output(), "!");
+
+    // 死循环可以被 pause 打断
+    let path = program("pause", "+[]");
+    let mut client = Client::start();
+    client.request("initialize", json!({}));
+    client.request("launch", json!({ "program": path }));
+    client.request("configurationDone", json!({}));
+    client.request("pause", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "pause");
+
+    let response = client.request("launch", json!({ "program": "/nonexistent.bf" }));
+    assert_eq!(response["success"], false);
+    let response = client.request("foo", json!({}));
+    assert_eq!(response["success"], false);
+    client.request("disconnect", json!({}));
+}
+
+#[test]
+fn runtime_errors_terminate_the_program_not_the_adapter() {
+    let path = program("abort", "++++++++[>++++++++<-]>+.[]");
+    let mut client = Client::start();
+    client.request("initialize", json!({}));
+    client.request("launch", json!({ "program": path, "maxSteps": 10000 }));
+    client.request("configurationDone", json!({}));
+    client.event("terminated");
+    assert_eq!(client.output(), "A");
+    let i = client.events.iter().position(|m| m["event"] == "output" && m["body"]["category"] == "stderr").unwrap();
+    assert_eq!(client.events.remove(i)["body"]["output"], "Aborted (step limit exceeded) after 10000 steps at pointer 1\n");
+    assert!(client.events.iter().all(|m| m["event"] != "exited"));
+
+    // 适配器还在处理请求，可以从头再执行一次
+    let response = client.request("stackTrace", json!({ "threadId": 1 }));
+    assert_eq!(response["success"], true);
+    let response = client.request("launch", json!({ "program": path, "maxSteps": 100 }));
+    assert_eq!(response["success"], true);
+    client.request("configurationDone", json!({}));
+    let error = loop {
+        let body = client.event("output");
+        if body["category"] == "stderr" {
+            break body;
+        }
+    };
+    assert_eq!(error["output"], "Aborted (step limit exceeded) after 100 steps at pointer 1\n");
+    client.request("disconnect", json!({}));
+}
+
+#[test]
+fn skips_messages_with_non_utf8_headers() {
+    let mut client = Client::start();
+    // 这条请求被跳过，没有回复
+    let body = json!({ "seq": 100, "type": "request", "command": "initialize", "arguments": {} }).to_string();
+    let mut data = b"X-Note: \xff\xfe\r\n".to_vec();
+    data.extend_from_slice(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_bytes());
+    client.writer.write_all(&data).unwrap();
+    client.writer.write_all(b"\xc3\x28\r\n\r\n").unwrap();
+
+    let response = client.request("initialize", json!({}));
+    assert_eq!(response["success"], true);
+    client.request("disconnect", json!({}));
+    assert!(client.events.iter().all(|m| m["request_seq"] != 100), "{:?}", client.events);
+}
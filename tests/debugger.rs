@@ -0,0 +1,231 @@
+use brainfuck_toy::debugger::{unescape, Breakpoint, Debugger, Hit, Stop, Watch, Watchpoint};
+use brainfuck_toy::interpreter_it::Interpreter;
+
+/// 在 repl 中执行命令，返回输出
+fn repl(data: &[u8], commands: &str) -> String {
+    let mut debugger = Debugger::new(data.to_vec()).unwrap();
+    let mut out = Vec::new();
+    debugger.repl(commands.as_bytes(), &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn repl_keeps_going_after_bad_commands() {
+    let data = std::fs::read("bf/hello_world.bf").unwrap();
+    let out = repl(&data, "s abc\nrs x\ngoto -1\ntape ?\ndelete x\nunwatch x\ninput \\x4\ninput \\xzz\nfoo\nc\n");
+    for usage in ["step [N]", "reverse-step [N]", "goto N", "tape [RADIUS]", "delete [ID]", "unwatch [ID]"] {
+        assert!(out.contains(&format!("error: Usage: {}\n", usage)), "{}", out);
+    }
+    assert!(out.contains("error: Invalid escape \\x4\n"), "{}", out);
+    assert!(out.contains("error: Invalid escape \\xzz\n"), "{}", out);
+    assert!(out.contains("Unknown command \"foo\""), "{}", out);
+    assert!(out.contains("Hello World!\n"), "{}", out);
+    assert!(out.contains("Program halted after"), "{}", out);
+}
+
+#[test]
+fn breakpoints_by_line_column_and_index() {
+    // 0 ADD(3)  1 LSB  2 SHR  3 ADD(2)  4 SHL  5 SUB(1)  6 RSB  7 SHR
+    let mut debugger = Debugger::new(b"+++\n[>++<-] loop\n>".to_vec()).unwrap();
+    assert_eq!(debugger.breakpoint_pcs(Breakpoint::Source { line: 2, column: None }), [1, 2, 3, 4, 5, 6]);
+    assert_eq!(debugger.breakpoint_pcs(Breakpoint::Source { line: 2, column: Some(3) }), [3]);
+    assert_eq!(debugger.breakpoint_pcs(Breakpoint::Source { line: 2, column: Some(5) }), [4]);
+    // 注释中的位置取后面的第一条指令
+    assert_eq!(debugger.breakpoint_pcs(Breakpoint::Source { line: 2, column: Some(9) }), [7]);
+    assert!(debugger.breakpoint_pcs(Breakpoint::Source { line: 4, column: None }).is_empty());
+    assert!(debugger.breakpoint_pcs(Breakpoint::Instruction(8)).is_empty());
+
+    let id = debugger.add_breakpoint(Breakpoint::Source { line: 2, column: Some(2) });
+    assert_eq!(debugger.continue_execution().unwrap(), Stop::Breakpoint(id));
+    assert_eq!(state(&debugger), (2, 0, 2, vec![3]));
+    assert_eq!(debugger.continue_execution().unwrap(), Stop::Breakpoint(id));
+    assert_eq!(state(&debugger), (2, 0, 7, vec![2, 2]));
+
+    debugger.clear_breakpoints();
+    let id = debugger.add_breakpoint(Breakpoint::Instruction(7));
+    assert_eq!(debugger.continue_execution().unwrap(), Stop::Breakpoint(id));
+    assert_eq!(state(&debugger), (7, 0, 17, vec![0, 6]));
+    assert_eq!(debugger.continue_execution().unwrap(), Stop::Halted);
+    assert_eq!(debugger.interpreter().pointer(), 1);
+}
+
+#[test]
+fn next_and_finish_run_whole_loops() {
+    // 0 ADD(2)  1 LSB  2 SHR  3 ADD(2)  4 LSB  5 SHR  6 ADD(1)  7 SHL  8 SUB(1)  9 RSB  10 SHL  11 SUB(1)  12 RSB  13 SHR
+    let mut debugger = Debugger::new(b"++[>++[>+<-]<-]>".to_vec()).unwrap();
+    assert_eq!(debugger.step_over().unwrap(), Stop::Step);
+    assert_eq!(debugger.interpreter().pc(), 1);
+    // 在 [ 上执行完整个循环，停在 ] 之后
+    assert_eq!(debugger.step_over().unwrap(), Stop::Step);
+    assert_eq!(state(&debugger), (13, 0, 34, vec![0, 0, 4]));
+
+    // 从内层循环体中执行完内层循环，再执行完外层循环
+    let mut debugger = Debugger::new(b"++[>++[>+<-]<-]>".to_vec()).unwrap();
+    let id = debugger.add_breakpoint(Breakpoint::Instruction(6));
+    assert_eq!(debugger.continue_execution().unwrap(), Stop::Breakpoint(id));
+    assert_eq!(debugger.loop_stack(), [1, 4]);
+    debugger.clear_breakpoints();
+    assert_eq!(debugger.step_out().unwrap(), Stop::Step);
+    assert_eq!(state(&debugger), (10, 1, 15, vec![2, 0, 2]));
+    assert_eq!(debugger.loop_stack(), [1]);
+    assert_eq!(debugger.step_out().unwrap(), Stop::Step);
+    assert_eq!(state(&debugger), (13, 0, 34, vec![0, 0, 4]));
+    assert!(debugger.loop_stack().is_empty());
+    // 不在循环中时执行到结束
+    assert_eq!(debugger.step_out().unwrap(), Stop::Halted);
+    assert_eq!(debugger.interpreter().pointer(), 1);
+}
+
+#[test]
+fn repl_reports_where_loops_and_tape() {
+    let out = repl(b"+++[>++<-]>", "s\nn\nw\nt 1\nrestart\nbreak 1:5\nc\nl\nd\nfinish\nc\n");
+    let expected = "\
+#0 ADD(3) at 1:1  +++
+(bf) #1 LSB(5) at 1:4  [
+(bf) #7 SHR(1) at 1:11  >
+(bf) #7 SHR(1) at 1:11  >
+pointer 0, steps 17
+(bf) >    0     1
+     0     6
+     .     .
+(bf) #0 ADD(3) at 1:1  +++
+(bf) Breakpoint 0 at Source { line: 1, column: Some(5) } (1 instructions)
+(bf) Breakpoint 0: #2 SHR(1) at 1:5  >
+(bf) #1 LSB(5) at 1:4  [
+(bf) (bf) #7 SHR(1) at 1:11  >
+(bf) Program halted after 18 steps
+(bf) ";
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn unescapes_input() {
+    assert_eq!(unescape("a\\nb\\t\\0\\\\\\x41\\x7f\\").unwrap(), b"a\nb\t\0\\A\x7f\\");
+    for bad in ["\\x", "\\x4", "\\xg1", "\\x+1"] {
+        assert!(unescape(bad).is_err(), "{}", bad);
+    }
+}
+
+/// 去掉末尾的零，反向执行不会缩短纸带
+fn trim(tape: &[u8]) -> Vec<u8> {
+    let len = tape.iter().rposition(|c| *c != 0).map_or(0, |i| i + 1);
+    tape[..len].to_vec()
+}
+
+/// 调试器当前的程序计数器、指针、步数和纸带
+fn state(debugger: &Debugger) -> (usize, usize, u64, Vec<u8>) {
+    let interpreter = debugger.interpreter();
+    (interpreter.pc(), interpreter.pointer(), interpreter.steps(), trim(interpreter.tape()))
+}
+
+#[test]
+fn reverse_steps_retrace_forward_steps() {
+    let data = std::fs::read("bf/hello_world.bf").unwrap();
+    let mut debugger = Debugger::new(data).unwrap();
+    let mut states = vec![state(&debugger)];
+    while debugger.step().unwrap() == Stop::Step {
+        states.push(state(&debugger));
+    }
+    states.push(state(&debugger));
+    assert_eq!(debugger.interpreter().output(), b"Hello World!\n");
+
+    states.pop();
+    while let Some(expected) = states.pop() {
+        let stop = debugger.reverse_step().unwrap();
+        assert_eq!(state(&debugger), expected, "{:?}", stop);
+    }
+    assert_eq!(debugger.reverse_step().unwrap(), Stop::Beginning);
+}
+
+#[test]
+fn reverse_steps_restore_input() {
+    let mut debugger = Debugger::new(b",[.,]".to_vec()).unwrap();
+    debugger.feed(b"ab\0");
+    assert_eq!(debugger.continue_execution().unwrap(), Stop::Halted);
+    assert_eq!(debugger.interpreter().output(), b"ab");
+    assert_eq!(debugger.reverse_continue().unwrap(), Stop::Beginning);
+    assert_eq!(debugger.interpreter().input().iter().copied().collect::<Vec<u8>>(), b"ab\0");
+    assert_eq!(debugger.continue_execution().unwrap(), Stop::Halted);
+}
+
+#[test]
+fn goto_replays_from_checkpoints() {
+    // 执行超过撤销记录的长度，回到更早的步数时从检查点重新执行
+    let data = b"-[>-[>++++++++[>+<-]<-]<-]".to_vec();
+    let mut debugger = Debugger::new(data.clone()).unwrap();
+    assert_eq!(debugger.continue_execution().unwrap(), Stop::Halted);
+    let total = debugger.interpreter().steps();
+    assert!(total > 1 << 21, "{}", total);
+
+    for steps in [total - 10, 1 << 20, 123_457, 70_000, 3, 0, total / 2] {
+        assert_eq!(debugger.goto(steps).unwrap(), Stop::Step);
+        let mut expected = Interpreter::default();
+        expected.load(data.clone()).unwrap();
+        expected.run_for(steps).unwrap();
+        assert_eq!(state(&debugger), (expected.pc(), expected.pointer(), steps, trim(expected.tape())), "goto {}", steps);
+
+        // 回到的位置之前可以继续反向单步
+        if steps > 0 {
+            debugger.reverse_step().unwrap();
+            let mut expected = Interpreter::default();
+            expected.load(data.clone()).unwrap();
+            expected.run_for(steps - 1).unwrap();
+            assert_eq!((debugger.interpreter().pc(), debugger.interpreter().steps()), (expected.pc(), steps - 1));
+        }
+    }
+}
+
+#[test]
+fn watchpoints_stop_on_the_matching_write() {
+    // 0 ADD(3)  1 LSB  2 SHR  3 ADD(2)  4 SHL  5 SUB(1)  6 RSB  7 SHR
+    let data = b"+++[>++<-]>".to_vec();
+
+    let mut debugger = Debugger::new(data.clone()).unwrap();
+    let id = debugger.add_watchpoint(Watchpoint { cell: 1, watch: Watch::Value(4) });
+    let stop = debugger.continue_execution().unwrap();
+    assert_eq!(stop, Stop::Watchpoint(Hit { id, pc: 3, cell: 1, old: 2, new: 4 }));
+    assert_eq!((debugger.interpreter().pc(), debugger.interpreter().tape()), (4, &[2, 4][..]));
+
+    // 反向执行停在写入之前
+    let stop = debugger.reverse_continue().unwrap();
+    assert_eq!(stop, Stop::Watchpoint(Hit { id, pc: 3, cell: 1, old: 2, new: 4 }));
+    assert_eq!((debugger.interpreter().pc(), debugger.interpreter().tape()), (3, &[2, 2][..]));
+
+    let mut debugger = Debugger::new(data.clone()).unwrap();
+    let id = debugger.add_watchpoint(Watchpoint { cell: 0, watch: Watch::Zero });
+    let stop = debugger.continue_execution().unwrap();
+    assert_eq!(stop, Stop::Watchpoint(Hit { id, pc: 5, cell: 0, old: 1, new: 0 }));
+    assert_eq!(debugger.interpreter().tape(), [0, 6]);
+
+    // 每次写入都停下，移动指针不算写入
+    let mut debugger = Debugger::new(data).unwrap();
+    let id = debugger.add_watchpoint(Watchpoint { cell: 1, watch: Watch::Write });
+    let mut hits = Vec::new();
+    while let Stop::Watchpoint(hit) = debugger.continue_execution().unwrap() {
+        assert_eq!(hit.id, id);
+        hits.push((hit.pc, hit.old, hit.new));
+    }
+    assert_eq!(hits, [(3, 0, 2), (3, 2, 4), (3, 4, 6)]);
+
+    // , 读入的值也是写入
+    let mut debugger = Debugger::new(b">,,".to_vec()).unwrap();
+    debugger.feed(b"xx");
+    let id = debugger.add_watchpoint(Watchpoint { cell: 1, watch: Watch::Value(b'x') });
+    assert_eq!(debugger.continue_execution().unwrap(), Stop::Watchpoint(Hit { id, pc: 1, cell: 1, old: 0, new: b'x' }));
+    assert_eq!(debugger.continue_execution().unwrap(), Stop::Watchpoint(Hit { id, pc: 2, cell: 1, old: b'x', new: b'x' }));
+    assert_eq!(debugger.continue_execution().unwrap(), Stop::Halted);
+}
+
+#[test]
+fn tape_window_with_maximal_radius() {
+    let mut debugger = Debugger::new(b"+>++>+++<".to_vec()).unwrap();
+    debugger.continue_execution().unwrap();
+    let window = debugger.tape_window(usize::MAX);
+    let lines: Vec<&str> = window.lines().collect();
+    assert!(lines[0].starts_with("     0>    1     2"), "{}", window);
+    assert!(lines[1].starts_with("     1     2     3"), "{}", window);
+    assert_eq!(brainfuck_toy::format_tape(&[7], 0, usize::MAX), ">    0\n     7\n     .");
+
+    let out = repl(b"+#", &format!("c\ntape {}\n", usize::MAX));
+    assert!(out.contains(">    0"), "{}", out);
+}
@@ -0,0 +1,141 @@
+use brainfuck_toy::differential::{check, execute, Difference, Divergence, Options, Outcome};
+use brainfuck_toy::engine::Engine;
+use brainfuck_toy::limit::Limits;
+
+/// 所有引擎的结果必须一致，不一致时输出第一处分歧
+fn assert_agree(data: &[u8], input: &[u8]) -> Vec<Outcome> {
+    assert_agree_with(data, input, &Options::default())
+}
+
+fn assert_agree_with(data: &[u8], input: &[u8], options: &Options) -> Vec<Outcome> {
+    match check(data, input, options) {
+        Ok(outcomes) => outcomes,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+fn program(name: &str) -> Vec<u8> {
+    std::fs::read(format!("bf/{}", name)).unwrap()
+}
+
+#[test]
+fn bundled_programs() {
+    let outcomes = assert_agree(&program("hello_world.bf"), b"");
+    assert_eq!(outcomes[0].output, b"Hello World!\n");
+
+    let outcomes = assert_agree(&program("to_upper.bf"), b"brainfuck\n");
+    assert_eq!(outcomes[0].output, b"BRAINFUCK");
+
+    let outcomes = assert_agree(&program("input.bf"), b"echo\0");
+    assert_eq!(outcomes[0].output, b"echo");
+
+    // 放入一个滑翔机后退出，演化一代在 debug 构建下要二十多秒
+    assert_agree(&program("life.bf"), b"ab\nbc\nca\ncb\ncc\nq\n");
+}
+
+#[test]
+fn pointer_stops_at_the_start_of_the_tape() {
+    // interpreter_it 把 <<< 合并为 SHL(3)，每个引擎都要停在 0 而不是越过纸带开头
+    let outcomes = assert_agree(b"+>++<<<<+++>>", b"");
+    assert_eq!(outcomes[0].tape, [4, 2]);
+    assert_eq!(outcomes[0].pointer, 2);
+
+    assert_agree(b">+<<<<<<<<<<[-]>[<<<<+>>>>-]<<<<<.", b"");
+    assert_agree(b"+[<<<+++++]>+.", b"");
+}
+
+#[test]
+fn cells_wrap() {
+    let outcomes = assert_agree(b"->+++++++++++++++++[>+++++++++++++++<-]>+.", b"");
+    assert_eq!(outcomes[0].tape, [255]);
+    assert_eq!(outcomes[0].output, [0]);
+}
+
+#[test]
+fn loops() {
+    let nested = format!("+{}-{}>+.", "[".repeat(100), "]".repeat(100));
+    assert_agree(nested.as_bytes(), b"");
+    // 清零、乘法和扫描循环
+    assert_agree(b"+++++[->+++<]>[-]+>+>+>+<<<<[>]<[[-]<]", b"");
+    assert_agree(b"++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.", b"");
+}
+
+#[test]
+fn unmatched_brackets() {
+    // 模糊测试发现的: interpreter 遇到没有关闭的 [ 会在 jump_table 中找不到跳转目标而 panic
+    for data in [&b"["[..], b"+[>+<-", b"+[]]", b"]"] {
+        let outcomes = assert_agree(data, b"");
+        assert!(outcomes.iter().all(|outcome| outcome.error.is_some()));
+    }
+}
+
+#[test]
+fn input_at_end_of_file() {
+    let outcomes = assert_agree(b",.,.,.", b"ab");
+    assert_eq!(outcomes[0].output, b"ab");
+    assert!(outcomes.iter().all(|outcome| outcome.error.as_deref() == Some("Unexpected end of input")), "{:?}", outcomes);
+}
+
+#[cfg(any(all(target_os = "linux", target_arch = "x86_64"), target_arch = "aarch64"))]
+#[test]
+fn jit_stops_at_the_end_of_its_tape() {
+    let data = ">".repeat(1 << 16);
+    let outcome = execute(Engine::Jit, format!("+{}+", data).as_bytes(), b"", &Options::default()).unwrap();
+    assert!(outcome.error.unwrap().contains("past the end"));
+    assert_eq!(outcome.tape, [1]);
+}
+
+#[test]
+fn limits_stop_every_engine() {
+    let options = Options { limits: Limits::default().max_steps(1000), ..Options::default() };
+    let outcomes = assert_agree_with(b"+.[]", b"", &options);
+    assert!(outcomes.iter().all(|outcome| outcome.aborted && outcome.output == [1]));
+
+    // 限制之内结束的程序不受影响
+    let outcomes = assert_agree_with(b"++++[>++++<-]>.", b"", &options);
+    assert!(outcomes.iter().all(|outcome| !outcome.aborted && outcome.error.is_none()));
+    assert_eq!(outcomes[0].output, [16]);
+}
+
+#[test]
+fn debug_instructions() {
+    // # 打断了 interpreter_it 的指令合并，启用后每个引擎的结果仍然一致
+    let options = Options { debug: Some(2), ..Options::default() };
+    let outcomes = assert_agree_with(b"+#+>++[<+#>-#]<.", b"", &options);
+    assert_eq!(outcomes[0].output, [4]);
+    assert_eq!(outcomes[0].tape, [4]);
+
+    // 没有启用时 # 是注释
+    let outcomes = assert_agree(b"+#+>++[<+#>-#]<.", b"");
+    assert_eq!(outcomes[0].output, [4]);
+}
+
+#[test]
+fn reports_the_first_divergence() {
+    let expected = Outcome::new(Engine::Naive, b"Hello World!\n".to_vec(), &[0, 87, 100, 33, 0], 1, None);
+    let actual = Outcome::new(Engine::Jit, b"Hello Wrld!\n".to_vec(), &[0, 87, 100, 33], 1, None);
+    let divergence = Divergence::find(&[expected.clone(), actual]).unwrap();
+    assert_eq!(divergence.difference, Difference::Output(7));
+    let report = divergence.to_string();
+    assert!(report.contains("naive and jit disagree at output byte 7"), "{}", report);
+    assert!(report.contains("\"Hello Wrld!\\n\""), "{}", report);
+
+    let actual = Outcome::new(Engine::It, b"Hello World!\n".to_vec(), &[0, 87, 101, 33], 1, None);
+    let divergence = Divergence::find(&[expected.clone(), actual]).unwrap();
+    assert_eq!(divergence.difference, Difference::Tape(2));
+
+    let actual = Outcome::new(Engine::It, b"Hello World!\n".to_vec(), &[0, 87, 100, 33], 0, None);
+    assert_eq!(Divergence::find(&[expected.clone(), actual]).unwrap().difference, Difference::Pointer);
+
+    let actual = Outcome::new(Engine::It, b"Hello World!\n".to_vec(), &[0, 87, 100, 33], 1, Some("failed".to_string()));
+    assert_eq!(Divergence::find(&[expected.clone(), actual]).unwrap().difference, Difference::Error);
+
+    // 中止时的状态不作比较，只比较是否中止
+    let aborted = Outcome { aborted: true, ..Outcome::new(Engine::It, Vec::new(), &[1], 0, Some("Aborted".to_string())) };
+    let other = Outcome { aborted: true, ..Outcome::new(Engine::Jit, b"H".to_vec(), &[2], 1, Some("Aborted".to_string())) };
+    assert!(Divergence::find(&[aborted.clone(), other]).is_none());
+    let failed = Outcome::new(Engine::Jit, Vec::new(), &[1], 0, Some("failed".to_string()));
+    assert_eq!(Divergence::find(&[aborted, failed]).unwrap().difference, Difference::Error);
+
+    assert!(Divergence::find(&[expected.clone(), expected]).is_none());
+}
@@ -0,0 +1,101 @@
+use brainfuck_toy::differential::{check, Options};
+use brainfuck_toy::formatter::Formatter;
+use brainfuck_toy::opcode;
+
+use std::path::PathBuf;
+
+fn programs() -> Vec<PathBuf> {
+    let mut programs = Vec::new();
+    for dir in ["bf", "tests/conformance"] {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|extension| extension == "bf") {
+                programs.push(path);
+            }
+        }
+    }
+    programs.sort();
+    programs
+}
+
+fn opcodes(data: &[u8]) -> Vec<u8> {
+    opcode::opcodes(data, false).map(|(_, u)| u).collect()
+}
+
+#[test]
+fn reindents_by_loop_depth() {
+    let formatted = Formatter::default().format(b"++++++++[>++++[>++>+++<<-]>+>+<<<-]>>.  print H\n\n\n>[-]").unwrap();
+    assert_eq!(
+        formatted,
+        "\
+++++++++
+[
+    >++++[>++>+++<<-]>+>+<<<-
+]
+>>.
+print H
+
+>[-]
+"
+    );
+
+    let formatted = Formatter::default().with_indent(2).with_comments(false).format(b"+[ outer [-] [ inner >[>]<- ] ]").unwrap();
+    assert_eq!(formatted, "+\n[\n  [-]\n  [\n    >[>]<-\n  ]\n]\n");
+}
+
+#[test]
+fn wraps_between_runs() {
+    let formatted = Formatter::default().with_width(16).format(&[b"+".repeat(10), b">".repeat(10), b"-".repeat(40)].concat()).unwrap();
+    let lines: Vec<&str> = formatted.lines().collect();
+    assert_eq!(lines, ["++++++++++", ">>>>>>>>>>", "----------------", "----------------", "--------"]);
+}
+
+#[test]
+fn idempotent_and_keeps_the_instructions() {
+    for path in programs() {
+        let data = std::fs::read(&path).unwrap();
+        for formatter in [Formatter::default(), Formatter::default().with_width(20).with_comments(false), Formatter::default().with_indent(0)] {
+            let formatted = formatter.format(&data).unwrap();
+            assert_eq!(opcodes(formatted.as_bytes()), opcodes(&data), "{}", path.display());
+            assert_eq!(formatter.format(formatted.as_bytes()).unwrap(), formatted, "{}", path.display());
+        }
+    }
+}
+
+#[test]
+fn formatted_programs_run_the_same() {
+    let data = std::fs::read("bf/hello_world.bf").unwrap();
+    let formatted = Formatter::default().format(&data).unwrap();
+    let outcomes = check(formatted.as_bytes(), b"", &Options::default()).unwrap();
+    assert_eq!(outcomes[0].output, b"Hello World!\n");
+}
+
+#[test]
+fn keeps_debug_instructions() {
+    // 和 bf fmt --strip-comments --dump -e '+#.' 一样，# 作为指令时去掉注释也要保留
+    let formatted = Formatter::default().with_comments(false).with_debug(true).format(b"+#.").unwrap();
+    assert_eq!(formatted, "+#.\n");
+    let formatted = Formatter::default().with_debug(true).format(b"+[>#<-] # here").unwrap();
+    assert_eq!(formatted, "+[>#<-]#\nhere\n");
+    assert_eq!(opcode::opcodes(formatted.as_bytes(), true).count(), 8);
+
+    // 没有启用时 # 是注释
+    let formatted = Formatter::default().with_comments(false).format(b"+#.").unwrap();
+    assert_eq!(formatted, "+.\n");
+}
+
+#[test]
+fn rejects_an_indent_larger_than_the_width() {
+    let e = Formatter::default().with_indent(usize::MAX).format(b"+[[-]>]").unwrap_err();
+    assert_eq!(e.to_string(), format!("Indent {} is larger than the width 80", usize::MAX));
+    let formatted = Formatter::default().with_width(16).with_indent(16).format(b"+[[-]>]").unwrap();
+    assert_eq!(formatted, format!("+\n[\n{}[-]>\n]\n", " ".repeat(16)));
+}
+
+#[test]
+fn rejects_unmatched_brackets() {
+    let e = Formatter::default().format(b"+[]\n]").unwrap_err();
+    assert_eq!(e.to_string(), "Unmatched ] at 2:1");
+    let e = Formatter::default().format(b"[[]").unwrap_err();
+    assert_eq!(e.to_string(), "Unmatched [ at 1:1");
+}
@@ -0,0 +1,87 @@
+use brainfuck_toy::differential::{execute, Options};
+use brainfuck_toy::engine::Engine;
+
+use std::path::Path;
+
+/// bf/ 下的程序和它们的期望输出: (tests/golden/ 下的名字, 程序, 是否只在编译执行的引擎上运行)
+const GOLDEN: [(&str, &str, bool); 6] = [
+    ("hello_world", "hello_world.bf", false),
+    ("input", "input.bf", false),
+    ("to_upper", "to_upper.bf", false),
+    ("life_glider", "life.bf", false), // 放入一个滑翔机后退出
+    ("life_generations", "life.bf", true), // 滑翔机演化一个周期，四代
+    ("mandelbrot", "mandelbrot.bf", true),
+];
+
+fn read(path: &Path) -> Option<Vec<u8>> {
+    std::fs::read(path).ok()
+}
+
+/// 在每个引擎上以 NAME.in (没有时为空) 为输入执行程序，输出必须和 NAME.out 完全相同。
+/// 有 NAME.err 时程序必须以包含它内容的错误结束，否则必须正常结束
+fn assert_golden(fixture: &Path, program: &Path, engines: &[Engine]) {
+    let data = read(program).unwrap();
+    let input = read(&fixture.with_extension("in")).unwrap_or_default();
+    let expected = read(&fixture.with_extension("out")).unwrap();
+    let error = read(&fixture.with_extension("err")).map(|e| String::from_utf8(e).unwrap().trim().to_string());
+
+    for &engine in engines {
+        let outcome = execute(engine, &data, &input, &Options::default()).unwrap();
+        let name = format!("{} on {}", program.display(), engine);
+        match (&error, &outcome.error) {
+            (Some(expected), Some(actual)) => assert!(actual.contains(expected.as_str()), "{}: expected error {:?}, got {:?}", name, expected, actual),
+            (None, None) => {}
+            (expected, actual) => panic!("{}: expected error {:?}, got {:?}", name, expected, actual),
+        }
+        if outcome.output != expected {
+            let i = outcome.output.iter().zip(&expected).position(|(a, b)| a != b).unwrap_or(outcome.output.len().min(expected.len()));
+            let from = i.saturating_sub(16);
+            panic!(
+                "{}: output differs from {} at byte {}\n  expected: \"{}\"\n  actual:   \"{}\"",
+                name,
+                fixture.with_extension("out").display(),
+                i,
+                expected[from..(i + 16).min(expected.len())].escape_ascii(),
+                outcome.output[from..(i + 16).min(outcome.output.len())].escape_ascii()
+            );
+        }
+    }
+}
+
+/// 两个解释器执行 mandelbrot.bf 和 life.bf 的演化在 debug 构建下要几十秒到几分钟
+fn is_interpreter(engine: &Engine) -> bool {
+    matches!(engine, Engine::Naive | Engine::It)
+}
+
+#[test]
+fn bundled_programs() {
+    for (name, program, slow) in GOLDEN {
+        let engines: Vec<Engine> = Engine::all().into_iter().filter(|engine| !slow || !is_interpreter(engine)).collect();
+        assert_golden(&Path::new("tests/golden").join(name), &Path::new("bf").join(program), &engines);
+    }
+}
+
+#[test]
+#[ignore = "slow in debug builds, run with cargo test --release -- --ignored"]
+fn bundled_programs_on_interpreters() {
+    for (name, program, slow) in GOLDEN {
+        if slow {
+            assert_golden(&Path::new("tests/golden").join(name), &Path::new("bf").join(program), &[Engine::Naive, Engine::It]);
+        }
+    }
+}
+
+/// tests/conformance/ 下的每个 NAME.bf 都是一个一致性测试: EOF、单元回绕和深层嵌套
+#[test]
+fn conformance() {
+    let mut programs: Vec<_> = std::fs::read_dir("tests/conformance")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "bf"))
+        .collect();
+    programs.sort();
+    assert!(!programs.is_empty());
+    for program in programs {
+        assert_golden(&program.with_extension(""), &program, &Engine::all());
+    }
+}
@@ -0,0 +1,206 @@
+use brainfuck_toy::interpreter_it::{Interpreter, ItOpcode, State};
+use brainfuck_toy::limit::{AbortReason, Aborted, CancelToken, Limits};
+
+use std::io::prelude::*;
+use std::sync::{Arc, Mutex};
+
+/// run 的一次读写
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Event {
+    Write(u8),
+    Read(u8),
+}
+
+/// 按顺序记录读写，写入第一个字节时取消执行
+#[derive(Clone, Default)]
+struct Log {
+    events: Arc<Mutex<Vec<Event>>>,
+    input: Arc<Mutex<Vec<u8>>>,
+    cancel: Option<CancelToken>,
+}
+
+impl Write for Log {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.events.lock().unwrap().extend(buf.iter().map(|c| Event::Write(*c)));
+        if let Some(cancel) = &self.cancel {
+            cancel.cancel();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Read for Log {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut input = self.input.lock().unwrap();
+        if input.is_empty() || buf.is_empty() {
+            return Ok(0);
+        }
+        let c = input.remove(0);
+        self.events.lock().unwrap().push(Event::Read(c));
+        buf[0] = c;
+        Ok(1)
+    }
+}
+
+#[test]
+fn steps_through_input_and_output() {
+    let mut interpreter = Interpreter::default();
+    interpreter.load(b"+++[>+<-]>.,[.,]".to_vec()).unwrap();
+
+    // ADD(3)
+    assert_eq!(interpreter.step().unwrap(), State::Running);
+    assert_eq!((interpreter.pc(), interpreter.steps(), interpreter.tape()), (1, 1, &[3][..]));
+
+    // [ 和 3 次循环体之后停在 > 之前
+    assert_eq!(interpreter.run_for(1 + 5 * 3).unwrap(), State::Running);
+    assert_eq!(interpreter.tape(), [0, 3]);
+    assert_eq!(interpreter.pointer(), 0);
+    assert_eq!(interpreter.step().unwrap(), State::Running);
+    assert_eq!(interpreter.pointer(), 1);
+    assert!(interpreter.output().is_empty());
+
+    assert_eq!(interpreter.run_until_input().unwrap(), State::NeedsInput);
+    let steps = interpreter.steps();
+    assert_eq!(interpreter.output(), [3]);
+    assert_eq!(interpreter.take_output(), [3]);
+    assert_eq!(interpreter.output_offset(), 1);
+
+    // 没有输入时停在 , 上，状态不变
+    assert_eq!(interpreter.step().unwrap(), State::NeedsInput);
+    assert_eq!(interpreter.steps(), steps);
+
+    interpreter.feed(b"ab");
+    assert_eq!(interpreter.run_until_input().unwrap(), State::NeedsInput);
+    assert_eq!(interpreter.take_output(), b"ab");
+    assert!(interpreter.input().is_empty());
+
+    interpreter.feed(&[0]);
+    assert_eq!(interpreter.run_until_input().unwrap(), State::Halted);
+    assert!(interpreter.take_output().is_empty());
+    assert_eq!(interpreter.output_offset(), 3);
+    assert_eq!(interpreter.step().unwrap(), State::Halted);
+}
+
+#[test]
+fn stops_at_debug_instructions() {
+    let mut interpreter = Interpreter::default().with_debug(2);
+    interpreter.load(b"+#+#+".to_vec()).unwrap();
+    assert_eq!(interpreter.run_until_input().unwrap(), State::Debug);
+    assert_eq!(interpreter.tape(), [1]);
+    assert_eq!(interpreter.run_until_input().unwrap(), State::Debug);
+    assert_eq!(interpreter.run_until_input().unwrap(), State::Halted);
+    assert_eq!(interpreter.tape(), [3]);
+}
+
+#[test]
+fn run_writes_output_before_reading_input() {
+    // 和逐条读写的解释器一样，每次读取之前的输出都已经写出去
+    let log = Log::default();
+    log.input.lock().unwrap().extend(b"xy");
+    let mut interpreter = Interpreter::default().with_input(log.clone()).with_output(log.clone());
+    interpreter.run(b"++++++++[>++++++++<-]>+.+.,.,.".to_vec()).unwrap();
+
+    let events = log.events.lock().unwrap().clone();
+    assert_eq!(
+        events,
+        [Event::Write(b'A'), Event::Write(b'B'), Event::Read(b'x'), Event::Write(b'x'), Event::Read(b'y'), Event::Write(b'y')]
+    );
+}
+
+#[test]
+fn run_writes_output_as_it_goes() {
+    // 输出之后进入不读取输入的死循环，输出必须马上写出去，不能等攒够一批
+    let cancel = CancelToken::new();
+    let log = Log { cancel: Some(cancel.clone()), ..Log::default() };
+    let mut interpreter = Interpreter::default().with_limits(Limits::default().cancel(cancel)).with_output(log.clone());
+
+    let aborted = interpreter.run(b"+.[]".to_vec()).unwrap_err().downcast::<Aborted>().unwrap();
+    assert_eq!(aborted.reason, AbortReason::Cancelled);
+    assert!(aborted.steps < 10, "output written after {} steps", aborted.steps);
+    assert_eq!(*log.events.lock().unwrap(), [Event::Write(1)]);
+}
+
+/// 执行到结束，返回全部输出
+fn run_to_end(interpreter: &mut Interpreter) -> Vec<u8> {
+    let mut output = interpreter.take_output();
+    assert_eq!(interpreter.run_until_input().unwrap(), State::Halted);
+    output.extend(interpreter.take_output());
+    output
+}
+
+#[test]
+fn restored_snapshots_continue_where_they_stopped() {
+    let data = std::fs::read("bf/hello_world.bf").unwrap();
+    let mut expected = Interpreter::default();
+    expected.load(data.clone()).unwrap();
+    let expected_output = run_to_end(&mut expected);
+
+    for steps in [0, 1, 50, 300, expected.steps() - 1, expected.steps()] {
+        let mut interpreter = Interpreter::default();
+        interpreter.load(data.clone()).unwrap();
+        interpreter.run_for(steps).unwrap();
+        // 一部分输出已经取走，一部分还留在快照中
+        let mut output = interpreter.take_output();
+        output.truncate(output.len() / 2);
+        let mut snapshot = Vec::new();
+        interpreter.save(&mut snapshot).unwrap();
+
+        let mut restored = Interpreter::default();
+        restored.restore(&snapshot[..]).unwrap();
+        assert_eq!((restored.pc(), restored.pointer(), restored.steps()), (interpreter.pc(), interpreter.pointer(), interpreter.steps()));
+        assert_eq!(restored.opcodes(), interpreter.opcodes());
+        for pc in 0..interpreter.opcodes().len() {
+            assert_eq!(restored.span(pc), interpreter.span(pc));
+        }
+
+        let _ = run_to_end(&mut interpreter);
+        let rest = run_to_end(&mut restored);
+        assert_eq!(restored.tape(), expected.tape(), "saved after {} steps", steps);
+        assert_eq!(restored.pointer(), expected.pointer());
+        assert_eq!(restored.steps(), expected.steps());
+        assert_eq!(restored.output_offset() as usize, expected_output.len());
+        let start = expected_output.len() - rest.len();
+        assert_eq!(rest, expected_output[start..], "saved after {} steps", steps);
+    }
+}
+
+#[test]
+fn restore_keeps_pending_input() {
+    let mut interpreter = Interpreter::default();
+    interpreter.load(b",.,.,.".to_vec()).unwrap();
+    interpreter.feed(b"abc");
+    interpreter.run_for(2).unwrap();
+    let mut snapshot = Vec::new();
+    interpreter.save(&mut snapshot).unwrap();
+
+    let mut restored = Interpreter::default();
+    restored.restore(&snapshot[..]).unwrap();
+    assert_eq!(run_to_end(&mut restored), b"abc");
+}
+
+#[test]
+fn restore_rejects_corrupted_snapshots() {
+    let mut interpreter = Interpreter::default();
+    interpreter.load(b"+[>+[-]<-]".to_vec()).unwrap();
+    let mut snapshot = Vec::new();
+    interpreter.save(&mut snapshot).unwrap();
+
+    // 每条指令占 5 个字节: 标记和 u32 参数，跟在 magic、版本、4 个 u64 和指令数之后
+    let opcode = |pc: usize| 6 + 2 + 8 * 4 + 8 + 5 * pc;
+    assert_eq!(interpreter.opcodes()[4], ItOpcode::LSB(5));
+    for (pc, target) in [(4, 4), (4, 100), (4, u32::MAX), (6, 0), (9, 4), (1, 5)] {
+        let mut corrupted = snapshot.clone();
+        corrupted[opcode(pc) + 1..opcode(pc) + 5].copy_from_slice(&u32::to_le_bytes(target));
+        let e = Interpreter::default().restore(&corrupted[..]).unwrap_err();
+        assert_eq!(e.to_string(), "Corrupted snapshot", "jump {} to {}", pc, target);
+    }
+
+    let e = Interpreter::default().restore(&snapshot[..snapshot.len() - 1]).unwrap_err();
+    assert!(e.downcast_ref::<std::io::Error>().is_some());
+    let e = Interpreter::default().restore(&b"BFSNAQ"[..]).unwrap_err();
+    assert_eq!(e.to_string(), "Not a snapshot file");
+}
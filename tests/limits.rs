@@ -0,0 +1,107 @@
+use brainfuck_toy::engine::{Engine, Options};
+use brainfuck_toy::limit::{AbortReason, Aborted, CancelToken, Limits};
+
+use std::time::Duration;
+
+/// 在引擎上执行程序，程序必须因超出限制被中止
+fn abort(engine: Engine, data: &[u8], limits: Limits) -> Aborted {
+    let options = Options {
+        limits,
+        input: Some(Box::new(std::io::empty())),
+        output: Some(Box::new(std::io::sink())),
+        ..Options::default()
+    };
+    match engine.run(data.to_vec(), options) {
+        Ok(()) => panic!("{} finished", engine),
+        Err(e) => *e.downcast::<Aborted>().unwrap_or_else(|e| panic!("{}: {}", engine, e)),
+    }
+}
+
+/// 执行机器码的引擎只在循环回跳处检查限制
+fn checks_at_back_edges(engine: Engine) -> bool {
+    matches!(engine, Engine::Tiered | Engine::Jit)
+}
+
+#[test]
+fn every_engine_stops_at_the_same_step() {
+    // 程序中没有连续相同的指令，naive 按源码和其他引擎按中间表计算的步数相同。
+    // +[>+] 第 k 次回跳后执行了 2 + 3k 步，指针为 k；限制落在回跳之后时所有引擎都在这里中止
+    for k in [1, 2, 100, 1000, 10000] {
+        for engine in Engine::all() {
+            let aborted = abort(engine, b"+[>+]", Limits::default().max_steps(2 + 3 * k));
+            assert_eq!(aborted.reason, AbortReason::StepLimit);
+            assert_eq!((aborted.steps, aborted.pointer), (2 + 3 * k, k as usize), "{}", engine);
+        }
+    }
+}
+
+#[test]
+fn machine_code_counts_the_same_steps() {
+    // 解释器在限制处准确中止；机器码可能多执行到下一次回跳，
+    // 这时的步数和指针必须和 interpreter_it 在同一步中止时相同
+    let data = b"+[>+++[>++<-]>[-<+>]<<]";
+    for max_steps in 0..300 {
+        for engine in Engine::all() {
+            let aborted = abort(engine, data, Limits::default().max_steps(max_steps));
+            if checks_at_back_edges(engine) {
+                assert!(aborted.steps >= max_steps, "{} with max_steps {}: {}", engine, max_steps, aborted);
+            } else if engine == Engine::It {
+                assert_eq!(aborted.steps, max_steps, "{}", engine);
+            }
+            if engine != Engine::Naive {
+                let expected = abort(Engine::It, data, Limits::default().max_steps(aborted.steps));
+                assert_eq!((aborted.steps, aborted.pointer), (expected.steps, expected.pointer), "{} with max_steps {}", engine, max_steps);
+            }
+        }
+    }
+}
+
+#[test]
+fn timeout_stops_every_engine() {
+    for engine in Engine::all() {
+        let aborted = abort(engine, b"+[]", Limits::default().timeout(Duration::from_millis(50)));
+        assert_eq!(aborted.reason, AbortReason::Timeout, "{}", engine);
+        assert_eq!(aborted.pointer, 0);
+        assert!(aborted.steps > 0);
+    }
+}
+
+#[test]
+fn cancel_stops_every_engine() {
+    // 在另一个线程中取消不会自己结束的循环，包括机器码中的嵌套循环
+    for data in [&b"+[]"[..], b"+[>+++[-]<]"] {
+        for engine in Engine::all() {
+            let cancel = CancelToken::new();
+            let canceller = {
+                let cancel = cancel.clone();
+                std::thread::spawn(move || {
+                    std::thread::sleep(Duration::from_millis(50));
+                    cancel.cancel();
+                })
+            };
+            let aborted = abort(engine, data, Limits::default().cancel(cancel));
+            canceller.join().unwrap();
+            assert_eq!(aborted.reason, AbortReason::Cancelled, "{}", engine);
+            assert!(aborted.pointer <= 1, "{}", engine);
+            assert!(aborted.steps > 0);
+        }
+    }
+}
+
+#[test]
+fn aborted_keeps_the_output_so_far() {
+    for engine in Engine::all() {
+        let aborted = abort(engine, b"++++++++[>++++++++<-]>+.+.[]", Limits::default().max_steps(10_000));
+        assert_eq!(aborted.output, b"AB", "{}", engine);
+        assert_eq!(aborted.pointer, 1);
+    }
+
+    // +[>.+<] 每次回跳执行 5 步，输出 0, 1, 2...，中止时只保留最后 64 KiB
+    let iterations: u64 = 100_000;
+    let expected: Vec<u8> = (iterations - 65536..iterations).map(|i| i as u8).collect();
+    for engine in Engine::all() {
+        let aborted = abort(engine, b"+[>.+<]", Limits::default().max_steps(2 + 5 * iterations));
+        assert_eq!(aborted.steps, 2 + 5 * iterations, "{}", engine);
+        assert!(aborted.output == expected, "{}: {} bytes of output", engine, aborted.output.len());
+    }
+}
@@ -0,0 +1,225 @@
+use brainfuck_toy::lsp;
+use brainfuck_toy::protocol::{read_message, write_message, InvalidMessage};
+
+use serde_json::{json, Value};
+
+const URI: &str = "file:///test.bf";
+
+/// 编码成带 Content-Length 头的消息
+fn encode(messages: &[Value]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for message in messages {
+        write_message(&mut data, message).unwrap();
+    }
+    data
+}
+
+/// 把 input 交给服务，返回服务的所有回复
+fn serve(input: &[u8]) -> Vec<Value> {
+    let mut output = Vec::new();
+    lsp::serve(input, &mut output).unwrap();
+    let mut output = &output[..];
+    let mut replies = Vec::new();
+    while let Some(message) = read_message(&mut output).unwrap() {
+        replies.push(message);
+    }
+    replies
+}
+
+fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn at(line: u64, character: u64) -> Value {
+    json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } })
+}
+
+fn range(start: (u64, u64), end: (u64, u64)) -> Value {
+    json!({ "start": { "line": start.0, "character": start.1 }, "end": { "line": end.0, "character": end.1 } })
+}
+
+/// id 对应的回复
+fn reply(replies: &[Value], id: u64) -> &Value {
+    replies.iter().find(|m| m["id"] == id).unwrap_or_else(|| panic!("no reply to {}", id))
+}
+
+#[test]
+fn diagnostics_hover_and_definition() {
+    let text = "+++++++[>+<-]\n]";
+    let replies = serve(&encode(&[
+        request(1, "initialize", json!({})),
+        json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+            "textDocument": { "uri": URI, "languageId": "bf", "version": 1, "text": text },
+        } }),
+        request(2, "textDocument/hover", at(0, 3)),
+        request(3, "textDocument/hover", at(0, 7)),
+        request(4, "textDocument/definition", at(0, 7)),
+        request(5, "textDocument/definition", at(0, 12)),
+        request(6, "textDocument/hover", at(u64::MAX, 0)),
+        request(7, "textDocument/hover", at(0, u64::MAX)),
+        request(8, "shutdown", json!(null)),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ]));
+
+    assert_eq!(reply(&replies, 1)["result"]["capabilities"]["hoverProvider"], true);
+
+    let diagnostics = replies.iter().find(|m| m["method"] == "textDocument/publishDiagnostics").unwrap();
+    assert_eq!(diagnostics["params"]["uri"], URI);
+    let diagnostics = diagnostics["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["range"], range((1, 0), (1, 1)));
+    assert_eq!(diagnostics[0]["message"], "Unmatched ]: no [ opens this loop");
+
+    let hover = &reply(&replies, 2)["result"];
+    assert_eq!(hover["contents"]["value"], "`ADD(7)`");
+    assert_eq!(hover["range"], range((0, 0), (0, 7)));
+    assert_eq!(reply(&replies, 3)["result"]["contents"]["value"], "multiply loop, 4 instructions");
+
+    assert_eq!(reply(&replies, 4)["result"], json!({ "uri": URI, "range": range((0, 12), (0, 13)) }));
+    assert_eq!(reply(&replies, 5)["result"], json!({ "uri": URI, "range": range((0, 7), (0, 8)) }));
+
+    // 超出文档的位置没有结果
+    assert_eq!(reply(&replies, 6)["result"], Value::Null);
+    assert_eq!(reply(&replies, 7)["result"], Value::Null);
+    assert_eq!(reply(&replies, 8)["result"], Value::Null);
+}
+
+#[test]
+fn invalid_messages_get_a_parse_error() {
+    let mut input = encode(&[request(1, "initialize", json!({}))]);
+    input.extend_from_slice(b"Content-Length: 5\r\n\r\n{bad}");
+    input.extend_from_slice(b"Content-Length: abc\r\n\r\n");
+    input.extend(encode(&[request(2, "shutdown", json!(null))]));
+    // 超过上限的内容被跳过，不会分配内存
+    input.extend_from_slice(b"Content-Length: 100000000\r\n\r\n{}");
+    let replies = serve(&input);
+
+    // 每条无效的消息一个 ParseError，之后的请求照常回复
+    assert_eq!(replies.len(), 5, "{:?}", replies);
+    assert_eq!(replies[0]["id"], 1);
+    for reply in &replies[1..3] {
+        assert_eq!(reply["id"], Value::Null);
+        assert_eq!(reply["error"]["code"], -32700);
+    }
+    assert_eq!(replies[3]["id"], 2);
+    assert_eq!(replies[4]["error"]["code"], -32700);
+    assert!(replies[4]["error"]["message"].as_str().unwrap().contains("exceeds"));
+}
+
+#[test]
+fn read_message_skips_invalid_bodies() {
+    let mut input = &b"Content-Length: 5\r\n\r\n{bad}Content-Length: 2\r\n\r\n{}"[..];
+    let e = read_message(&mut input).unwrap_err();
+    assert!(e.is::<InvalidMessage>(), "{}", e);
+    assert_eq!(read_message(&mut input).unwrap(), Some(json!({})));
+    assert_eq!(read_message(&mut input).unwrap(), None);
+}
+
+fn open(text: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {
+        "textDocument": { "uri": URI, "languageId": "bf", "version": 1, "text": text },
+    } })
+}
+
+fn document() -> Value {
+    json!({ "textDocument": { "uri": URI } })
+}
+
+#[test]
+fn folding_ranges_and_symbols() {
+    let text = "++[ outer\n  >+[>+<-]\n  <-]\n[-]\n+[>\n]";
+    let replies = serve(&encode(&[
+        open(text),
+        request(1, "textDocument/foldingRange", document()),
+        request(2, "textDocument/documentSymbol", document()),
+    ]));
+
+    // 只有跨行的循环可以折叠
+    assert_eq!(
+        reply(&replies, 1)["result"],
+        json!([{ "startLine": 0, "endLine": 2, "kind": "region" }, { "startLine": 4, "endLine": 5, "kind": "region" }])
+    );
+
+    // 每个顶层循环一个符号，内层循环不单独列出
+    let symbols = reply(&replies, 2)["result"].as_array().unwrap();
+    let names: Vec<&str> = symbols.iter().map(|s| s["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["loop 1:3", "loop 4:1", "loop 5:2"]);
+    assert_eq!(symbols[0]["range"], range((0, 2), (2, 5)));
+    assert_eq!(symbols[0]["selectionRange"], range((0, 2), (0, 3)));
+    assert_eq!(symbols[1]["range"], range((3, 0), (3, 3)));
+    assert_eq!(symbols[2]["range"], range((4, 1), (5, 1)));
+    assert_eq!(symbols[1]["detail"], "clear loop");
+}
+
+#[test]
+fn changes_and_closes_documents() {
+    let change = json!({ "jsonrpc": "2.0", "method": "textDocument/didChange", "params": {
+        "textDocument": { "uri": URI, "version": 2 },
+        "contentChanges": [{ "text": "+[>\n]" }, { "text": "+\n[" }],
+    } });
+    let close = json!({ "jsonrpc": "2.0", "method": "textDocument/didClose", "params": document() });
+    let replies = serve(&encode(&[
+        open("+[-]"),
+        change,
+        request(1, "textDocument/hover", at(1, 0)),
+        request(2, "textDocument/foldingRange", document()),
+        close,
+        request(3, "textDocument/hover", at(0, 0)),
+    ]));
+
+    let diagnostics: Vec<&Value> = replies.iter().filter(|m| m["method"] == "textDocument/publishDiagnostics").collect();
+    assert_eq!(diagnostics.len(), 3);
+    assert_eq!(diagnostics[0]["params"]["diagnostics"], json!([]));
+    // 全量同步，使用最后一次修改的文本
+    let unmatched = diagnostics[1]["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(unmatched.len(), 1);
+    assert_eq!(unmatched[0]["range"], range((1, 0), (1, 1)));
+    assert_eq!(unmatched[0]["message"], "Unmatched [: no ] closes this loop");
+    assert_eq!(reply(&replies, 1)["result"]["contents"]["value"], "unmatched [");
+    assert_eq!(reply(&replies, 2)["result"], json!([]));
+
+    // 关闭后清除诊断，文档不再可用
+    assert_eq!(diagnostics[2]["params"]["diagnostics"], json!([]));
+    assert_eq!(reply(&replies, 3)["result"], Value::Null);
+}
+
+#[test]
+fn columns_count_utf16_units() {
+    // 注、释各占一个 UTF-16 单元，😀 占两个，+ 在第 5 列，字节偏移是 11
+    let replies = serve(&encode(&[
+        open("注释😀 +[-]"),
+        request(1, "textDocument/hover", at(0, 5)),
+        request(2, "textDocument/definition", at(0, 6)),
+        request(3, "textDocument/hover", at(0, 3)),
+        request(4, "textDocument/documentSymbol", document()),
+    ]));
+
+    let hover = &reply(&replies, 1)["result"];
+    assert_eq!(hover["contents"]["value"], "`ADD(1)`");
+    assert_eq!(hover["range"], range((0, 5), (0, 6)));
+    assert_eq!(reply(&replies, 2)["result"]["range"], range((0, 8), (0, 9)));
+    // 😀 中间的位置落在注释中
+    assert_eq!(reply(&replies, 3)["result"], Value::Null);
+    assert_eq!(reply(&replies, 4)["result"][0]["range"], range((0, 6), (0, 9)));
+}
+
+#[test]
+fn non_utf8_headers_get_a_parse_error() {
+    let mut input = encode(&[request(1, "initialize", json!({}))]);
+    let skipped = request(2, "shutdown", json!(null)).to_string();
+    input.extend_from_slice(b"X-Note: \xff\xfe\r\n");
+    input.extend_from_slice(format!("Content-Length: {}\r\n\r\n{}", skipped.len(), skipped).as_bytes());
+    input.extend(encode(&[request(3, "shutdown", json!(null))]));
+    let replies = serve(&input);
+
+    assert_eq!(replies.len(), 3, "{:?}", replies);
+    assert_eq!(replies[0]["id"], 1);
+    assert_eq!(replies[1]["id"], Value::Null);
+    assert_eq!(replies[1]["error"]["code"], -32700);
+    assert!(replies[1]["error"]["message"].as_str().unwrap().contains("UTF-8"));
+    assert_eq!(replies[2]["id"], 3);
+
+    let mut input = &b"\xc3\x28\r\n\r\nContent-Length: 2\r\n\r\n{}"[..];
+    assert!(read_message(&mut input).unwrap_err().is::<InvalidMessage>());
+    assert_eq!(read_message(&mut input).unwrap(), Some(json!({})));
+}
@@ -0,0 +1,86 @@
+use brainfuck_toy::interpreter_it::{Interpreter, ItOpcode};
+use brainfuck_toy::profile::{classify, LoopKind, LoopProfile, Profile};
+
+/// 执行程序，返回每条指令的执行次数
+fn run_profiled(data: &[u8]) -> Profile {
+    let mut interpreter = Interpreter::default().with_profile().with_output(std::io::sink());
+    interpreter.run(data.to_vec()).unwrap();
+    interpreter.profile().unwrap()
+}
+
+/// 循环体的中间表指令
+fn body(body: &str) -> Vec<ItOpcode> {
+    let mut interpreter = Interpreter::default();
+    interpreter.load(format!("[{}]", body).into_bytes()).unwrap();
+    let opcodes = interpreter.opcodes();
+    opcodes[1..opcodes.len() - 1].to_vec()
+}
+
+#[test]
+fn classifies_loops() {
+    for (source, kind) in [
+        ("-", LoopKind::Clear),
+        ("+", LoopKind::Clear),
+        ("---", LoopKind::Clear),
+        ("--", LoopKind::General),
+        (">", LoopKind::Scan),
+        ("<<<", LoopKind::Scan),
+        ("->+<", LoopKind::Multiply),
+        ("->++>+++<<", LoopKind::Multiply),
+        ("<->+", LoopKind::Multiply),
+        ("+>---<", LoopKind::Multiply),
+        ("->+", LoopKind::General),
+        ("-->+<", LoopKind::General),
+        ("->.<", LoopKind::General),
+        ("-,", LoopKind::General),
+        ("->[-]<", LoopKind::General),
+        ("", LoopKind::General),
+    ] {
+        assert_eq!(classify(&body(source)), kind, "[{}]", source);
+    }
+}
+
+#[test]
+fn counts_loop_entries_and_iterations() {
+    // 0 ADD(2)  1 LSB  2 SUB  3 SHR  4 ADD(3)  5 SHL  6 RSB  7 SHR  8 LSB  9 SUB  10 RSB
+    let profile = run_profiled(b"++[->+++<]>[-]");
+    assert_eq!(profile.counts(), [1, 1, 2, 2, 2, 2, 2, 1, 1, 6, 6]);
+    assert_eq!(profile.total(), 26);
+    assert_eq!(
+        profile.loops(),
+        [
+            LoopProfile { lsb: 1, rsb: 6, entries: 1, iterations: 2, instructions: 11, own: 11, kind: LoopKind::Multiply },
+            LoopProfile { lsb: 8, rsb: 10, entries: 1, iterations: 6, instructions: 13, own: 13, kind: LoopKind::Clear },
+        ]
+    );
+
+    // 内层循环执行的指令不算在外层循环自身的指令数中
+    let profile = run_profiled(b"++[>+++[-]<-]");
+    let loops = profile.hot_loops();
+    assert_eq!(loops.len(), 2);
+    assert_eq!((loops[0].lsb, loops[0].entries, loops[0].iterations, loops[0].own, loops[0].kind), (4, 2, 6, 14, LoopKind::Clear));
+    assert_eq!((loops[1].lsb, loops[1].entries, loops[1].iterations, loops[1].own, loops[1].kind), (1, 1, 2, 11, LoopKind::General));
+    assert_eq!(loops[1].instructions, 25);
+}
+
+/// 折叠栈格式的输出
+fn folded(profile: &Profile, source: &[u8]) -> String {
+    let mut out = Vec::new();
+    profile.folded(source, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn folds_stacks_by_loop_nesting() {
+    let source = b"++[>+++[-]<-]";
+    assert_eq!(folded(&run_profiled(source), source), "main 1\nmain;1:3 11\nmain;1:3;1:8 14\n");
+
+    // [ 判断为零时跳到 ]，两条指令都算在循环中
+    let source = b"+[-]\n>[+]\n>>+[-]";
+    let profile = run_profiled(source);
+    assert_eq!(folded(&profile, source), "main 4\nmain;1:2 3\nmain;2:2 2\nmain;3:4 3\n");
+
+    // 没有源码位置时用指令下标命名
+    let profile = Profile::new(profile.opcodes().to_vec(), Vec::new(), profile.counts().to_vec());
+    assert_eq!(folded(&profile, b""), "main 4\nmain;#1 3\nmain;#5 2\nmain;#10 3\n");
+}
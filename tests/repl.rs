@@ -0,0 +1,93 @@
+use brainfuck_toy::interpreter_it::Interpreter;
+use brainfuck_toy::repl::Repl;
+
+fn trim(tape: &[u8]) -> Vec<u8> {
+    let len = tape.iter().rposition(|c| *c != 0).map_or(0, |i| i + 1);
+    tape[..len].to_vec()
+}
+
+#[test]
+fn failed_lines_leave_the_tape_and_session_unchanged() {
+    let mut repl = Repl::default();
+    let mut out = Vec::new();
+    repl.eval(b"+++>++", &mut &b""[..], &mut out).unwrap();
+    repl.eval(b",", &mut &b"A\n"[..], &mut out).unwrap();
+
+    // 第一个 , 读取上一次多读入的换行，第二个 , 执行时输入结束
+    let e = repl.eval(b"<+++++>>+++,,", &mut &b""[..], &mut out).unwrap_err();
+    assert_eq!(e.to_string(), "Unexpected end of input");
+    assert_eq!(trim(repl.interpreter().tape()), [3, b'A']);
+    assert_eq!(repl.interpreter().pointer(), 1);
+    // 多读入的换行仍然留在输入中
+    assert_eq!(repl.interpreter().input().iter().copied().collect::<Vec<u8>>(), b"\n");
+    assert_eq!(repl.session(), b"+++>++\n,\n");
+
+    // 保存的会话在新的纸带上得到相同的结果
+    let mut interpreter = Interpreter::default().with_input(&b"A\n"[..]).with_output(std::io::sink());
+    interpreter.run(repl.session().to_vec()).unwrap();
+    assert_eq!(trim(interpreter.tape()), trim(repl.interpreter().tape()));
+    assert_eq!(interpreter.pointer(), repl.interpreter().pointer());
+
+    repl.eval(b"+", &mut &b""[..], &mut out).unwrap();
+    assert_eq!(trim(repl.interpreter().tape()), [3, b'B']);
+}
+
+/// 用脚本作为输入执行 run，返回输出
+fn run(repl: &mut Repl, script: &str) -> String {
+    let mut out = Vec::new();
+    repl.run(script.as_bytes(), &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn pending_lines_and_commands() {
+    let mut repl = Repl::default();
+    let out = run(&mut repl, "+++[>+\n  ++<-]\n>.\n]+\n:tape\n:tape 0\n:tape x\n:foo\n");
+    let expected = "\
+Type brainfuck to run it, :help for commands
+bf> ... >    0     1
+     0     9
+     .     .
+bf> \t
+     0>    1
+     0     9
+     .     .
+bf> error: unmatched ] at 1:1
+bf> pointer 1
+     0>    1
+     0     9
+     .     .
+bf> pointer 1
+>    1
+     9
+     .
+bf> error: invalid digit found in string
+bf> Unknown command \":foo\", try :help
+bf> 
+";
+    assert_eq!(out, expected);
+    // 出错的行和命令不加入会话
+    assert_eq!(repl.session(), b"+++[>+\n  ++<-]\n>.\n");
+}
+
+#[test]
+fn reset_save_and_load() {
+    let path = std::env::temp_dir().join(format!("bf_repl_{}.bf", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    let mut repl = Repl::default();
+    let out = run(&mut repl, &format!("++>+\n:save {}\n:reset\n:tape\n", path));
+    assert!(out.contains(&format!("Saved 5 bytes to {}\n", path)), "{}", out);
+    assert!(out.contains("Tape and session cleared\nbf> pointer 0\n>    0\n     0\n     .\n"), "{}", out);
+    assert!(repl.session().is_empty());
+    assert!(trim(repl.interpreter().tape()).is_empty());
+    assert_eq!(std::fs::read(path).unwrap(), b"++>+\n");
+
+    // :quit 之后的行不执行
+    let out = run(&mut repl, &format!(":load {}\n:quit\n+\n", path));
+    std::fs::remove_file(path).unwrap();
+    assert!(out.ends_with("bf> "), "{}", out);
+    assert_eq!(trim(repl.interpreter().tape()), [2, 1]);
+    assert_eq!(repl.interpreter().pointer(), 1);
+    assert_eq!(repl.session(), b"++>+\n");
+}
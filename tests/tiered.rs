@@ -0,0 +1,107 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+use brainfuck_toy::differential::Capture;
+use brainfuck_toy::interpreter_it;
+use brainfuck_toy::limit::{Aborted, Limits};
+use brainfuck_toy::tiered;
+
+/// 执行结果: 输出、去掉末尾零的纸带、指针、中止时的步数
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Outcome {
+    output: Vec<u8>,
+    tape: Vec<u8>,
+    pointer: usize,
+    aborted: Option<u64>,
+}
+
+fn outcome(result: Result<(), Box<dyn std::error::Error>>, output: Capture, tape: &[u8], pointer: usize) -> Outcome {
+    let aborted = result.map_err(|e| e.downcast::<Aborted>().unwrap().steps).err();
+    let len = tape.iter().rposition(|c| *c != 0).map_or(0, |i| i + 1);
+    Outcome { output: output.take(), tape: tape[..len].to_vec(), pointer, aborted }
+}
+
+fn run_it(data: &[u8], limits: Limits) -> Outcome {
+    let output = Capture::default();
+    let mut interpreter = interpreter_it::Interpreter::default().with_limits(limits).with_input(std::io::empty()).with_output(output.clone());
+    let result = interpreter.run(data.to_vec());
+    outcome(result, output, interpreter.tape(), interpreter.pointer())
+}
+
+/// 返回结果和编译的热点循环数
+fn run_tiered(data: &[u8], threshold: u32, limits: Limits) -> (Outcome, usize) {
+    let output = Capture::default();
+    let mut interpreter = tiered::Interpreter::default()
+        .with_threshold(threshold)
+        .with_limits(limits)
+        .with_input(std::io::empty())
+        .with_output(output.clone());
+    let result = interpreter.run(data.to_vec());
+    (outcome(result, output, interpreter.tape(), interpreter.pointer()), interpreter.compiled_loops())
+}
+
+#[test]
+fn hot_loops_are_compiled() {
+    let data = b"++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+    let expected = run_it(data, Limits::default());
+    assert_eq!(expected.output, b"Hello World!\n");
+
+    // 阈值为 1 时每个循环第一次回跳就被编译: 两个内层循环先被编译，然后是外层循环
+    let (actual, compiled) = run_tiered(data, 1, Limits::default());
+    assert_eq!(actual, expected);
+    assert_eq!(compiled, 3);
+
+    // 外层循环只回跳 7 次，不会被编译；内层的 [>++>+++>+++>+<<<<-] 一共回跳 24 次
+    let (actual, compiled) = run_tiered(data, 20, Limits::default());
+    assert_eq!(actual, expected);
+    assert!((1..3).contains(&compiled), "{}", compiled);
+
+    let (actual, compiled) = run_tiered(data, u32::MAX, Limits::default());
+    assert_eq!(actual, expected);
+    assert_eq!(compiled, 0);
+
+    // 含有 I/O 的循环不编译
+    let (actual, compiled) = run_tiered(b"+++++[.-]", 1, Limits::default());
+    assert_eq!(actual.output, [5, 4, 3, 2, 1]);
+    assert_eq!(compiled, 0);
+}
+
+#[test]
+fn exits_in_the_middle_of_a_loop() {
+    // 编译后的循环只在回跳处检查步数，可能在超过限制后的第一次回跳处才回到解释器中止。
+    // 中止时的状态必须和 interpreter_it 在同一步中止时相同
+    let data = b"+++[>++[>+++<-]>[-<+>]<<-]>[-]+++++[<+>-]<";
+    let total = run_it(data, Limits::default());
+    for max_steps in 0..200 {
+        for threshold in [1, 2, 3] {
+            let (actual, _) = run_tiered(data, threshold, Limits::default().max_steps(max_steps));
+            let expected = match actual.aborted {
+                Some(steps) => {
+                    assert!(steps >= max_steps, "max_steps {} threshold {}: aborted after {}", max_steps, threshold, steps);
+                    run_it(data, Limits::default().max_steps(steps))
+                }
+                None => total.clone(),
+            };
+            assert_eq!(actual, expected, "max_steps {} threshold {}", max_steps, threshold);
+        }
+    }
+
+    // < 越过纸带开头时回到解释器，由解释器停在 0 后回到机器码
+    let (actual, compiled) = run_tiered(b"+++++[<-]", 1, Limits::default());
+    assert_eq!(actual, run_it(b"+++++[<-]", Limits::default()));
+    assert_eq!(compiled, 1);
+}
+
+#[test]
+fn extends_the_tape_when_shr_passes_its_end() {
+    // 指针每次越过纸带末尾都回到解释器扩展纸带，再回到机器码继续执行
+    let step = ">".repeat(32);
+    let data = format!("-[[-{}+{}]{}-]", step, "<".repeat(32), step);
+    let data = data.as_bytes();
+    let expected = run_it(data, Limits::default());
+    assert_eq!(expected.pointer, 255 * 32);
+    for threshold in [1, 2, 100] {
+        let (actual, compiled) = run_tiered(data, threshold, Limits::default());
+        assert_eq!(actual, expected, "threshold {}", threshold);
+        assert!(compiled > 0);
+    }
+}
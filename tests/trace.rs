@@ -0,0 +1,112 @@
+use brainfuck_toy::differential::Capture;
+use brainfuck_toy::interpreter;
+use brainfuck_toy::interpreter_it;
+use brainfuck_toy::trace::{Event, Format, Tracer};
+
+use serde_json::Value;
+
+/// 解析 JSON 格式的执行记录
+fn parse_json(data: &[u8]) -> Vec<Event> {
+    let text = std::str::from_utf8(data).unwrap();
+    text.lines()
+        .map(|line| {
+            let v: Value = serde_json::from_str(line).unwrap();
+            Event {
+                step: v["step"].as_u64().unwrap(),
+                pc: v["pc"].as_u64().unwrap() as usize,
+                offset: v["offset"].as_u64().map(|offset| offset as usize),
+                pointer: v["pointer"].as_u64().unwrap() as usize,
+                before: v["before"].as_u64().unwrap() as u8,
+                after: v["after"].as_u64().unwrap() as u8,
+            }
+        })
+        .collect()
+}
+
+/// 解析二进制格式的执行记录
+fn parse_binary(data: &[u8]) -> Vec<Event> {
+    assert_eq!(&data[..9], b"BFTRACE\x02\x00");
+    let records = &data[9..];
+    assert_eq!(records.len() % 34, 0);
+    let u64_at = |record: &[u8], i: usize| u64::from_le_bytes(record[i..i + 8].try_into().unwrap());
+    records
+        .chunks(34)
+        .map(|record| Event {
+            step: u64_at(record, 0),
+            pc: u64_at(record, 8) as usize,
+            offset: Some(u64_at(record, 16)).filter(|offset| *offset != u64::MAX).map(|offset| offset as usize),
+            pointer: u64_at(record, 24) as usize,
+            before: record[32],
+            after: record[33],
+        })
+        .collect()
+}
+
+fn trace_it(data: &[u8], format: Format, configure: impl Fn(Tracer) -> Tracer) -> Vec<u8> {
+    let out = Capture::default();
+    let tracer = configure(Tracer::new(out.clone(), format));
+    let mut interpreter = interpreter_it::Interpreter::default().with_trace(tracer).with_output(std::io::sink());
+    interpreter.run(data.to_vec()).unwrap();
+    out.take()
+}
+
+fn trace_naive(data: &[u8], format: Format) -> Vec<u8> {
+    let out = Capture::default();
+    let mut interpreter = interpreter::Interpreter::default().with_trace(Tracer::new(out.clone(), format)).with_output(std::io::sink());
+    interpreter.run(data.to_vec()).unwrap();
+    out.take()
+}
+
+#[test]
+fn records_every_instruction() {
+    // 0 ADD(2)  1 LSB  2 SHR  3 ADD(1)  4 SHL  5 SUB(1)  6 RSB
+    let data = b"++[>+<-]";
+    let json = trace_it(data, Format::Json, |tracer| tracer);
+    let first = std::str::from_utf8(&json).unwrap().lines().next().unwrap();
+    assert_eq!(first, r#"{"step":0,"pc":0,"offset":0,"pointer":0,"before":0,"after":2}"#);
+
+    let events = parse_json(&json);
+    let pcs: Vec<usize> = events.iter().map(|event| event.pc).collect();
+    assert_eq!(pcs, [0, 1, 2, 3, 4, 5, 6, 2, 3, 4, 5, 6]);
+    assert!(events.iter().enumerate().all(|(i, event)| event.step == i as u64));
+    assert_eq!(events[3], Event { step: 3, pc: 3, offset: Some(4), pointer: 1, before: 0, after: 1 });
+    assert_eq!(events[11], Event { step: 11, pc: 6, offset: Some(7), pointer: 0, before: 0, after: 0 });
+
+    assert_eq!(parse_binary(&trace_it(data, Format::Binary, |tracer| tracer)), events);
+
+    // naive 按源码逐个字符执行，pc 就是源码偏移
+    let events = parse_json(&trace_naive(data, Format::Json));
+    assert_eq!(events.len(), 13);
+    assert!(events.iter().all(|event| event.offset == Some(event.pc)));
+    assert_eq!(parse_binary(&trace_naive(data, Format::Binary)), events);
+}
+
+#[test]
+fn filters_by_range_and_sampling() {
+    let data = b"++[>+<-]";
+    let events = parse_json(&trace_it(data, Format::Json, |tracer| tracer.with_range(3..5)));
+    let pcs: Vec<usize> = events.iter().map(|event| event.pc).collect();
+    assert_eq!(pcs, [2, 3, 2, 3]);
+
+    let events = parse_json(&trace_it(data, Format::Json, |tracer| tracer.with_sampling(5)));
+    let steps: Vec<u64> = events.iter().map(|event| event.step).collect();
+    assert_eq!(steps, [0, 5, 10]);
+
+    let events = parse_binary(&trace_it(data, Format::Binary, |tracer| tracer.with_range(3..5).with_sampling(2)));
+    let steps: Vec<u64> = events.iter().map(|event| event.step).collect();
+    assert_eq!(steps, [2, 7]);
+
+    // 没有记录时二进制格式也有文件头
+    assert_eq!(trace_it(data, Format::Binary, |tracer| tracer.with_range(100..200)), b"BFTRACE\x02\x00");
+}
+
+#[test]
+fn binary_records_keep_large_values() {
+    let out = Capture::default();
+    let mut tracer = Tracer::new(out.clone(), Format::Binary);
+    let event = Event { step: u64::MAX - 1, pc: 1 << 40, offset: Some(u32::MAX as usize + 1), pointer: 1 << 33, before: 255, after: 0 };
+    tracer.record(&event).unwrap();
+    tracer.record(&Event { offset: None, ..event }).unwrap();
+    tracer.flush().unwrap();
+    assert_eq!(parse_binary(&out.take()), [event, Event { offset: None, ..event }]);
+}
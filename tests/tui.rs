@@ -0,0 +1,146 @@
+#![cfg(feature = "tui")]
+
+use brainfuck_toy::tui::{App, MAX_SPEED};
+
+use ratatui::backend::TestBackend;
+use ratatui::crossterm::event::KeyCode;
+use ratatui::Terminal;
+
+fn press(app: &mut App, keys: &[KeyCode]) {
+    for key in keys {
+        assert!(app.handle_key(*key).unwrap());
+    }
+}
+
+fn chars(s: &str) -> Vec<KeyCode> {
+    s.chars().map(KeyCode::Char).collect()
+}
+
+#[test]
+fn space_toggles_play_and_pause() {
+    let mut app = App::new(b"+[]".to_vec()).unwrap();
+    assert!(!app.is_playing());
+    press(&mut app, &[KeyCode::Char(' ')]);
+    assert!(app.is_playing());
+    app.tick().unwrap();
+    assert_eq!(app.status(), "Playing");
+    app.tick().unwrap();
+    assert_eq!(app.interpreter().steps(), 2);
+
+    press(&mut app, &[KeyCode::Char(' ')]);
+    assert!(!app.is_playing());
+    assert_eq!(app.status(), "Paused");
+    // 暂停时不执行
+    app.tick().unwrap();
+    assert_eq!(app.interpreter().steps(), 2);
+
+    assert!(!app.handle_key(KeyCode::Char('q')).unwrap());
+}
+
+#[test]
+fn s_steps_one_instruction() {
+    let mut app = App::new(b"+++>".to_vec()).unwrap();
+    press(&mut app, &[KeyCode::Char(' '), KeyCode::Char('s')]);
+    assert!(!app.is_playing());
+    assert_eq!((app.interpreter().steps(), app.interpreter().pc(), app.interpreter().pointer()), (1, 1, 0));
+    assert_eq!(app.interpreter().tape()[0], 3);
+
+    press(&mut app, &[KeyCode::Right]);
+    assert_eq!((app.interpreter().steps(), app.interpreter().pointer()), (2, 1));
+    assert_eq!(app.status(), "Halted");
+    press(&mut app, &[KeyCode::Char('s')]);
+    assert_eq!(app.interpreter().steps(), 2);
+}
+
+#[test]
+fn speed_stays_between_one_and_max() {
+    let mut app = App::new(b"+".to_vec()).unwrap();
+    assert_eq!(app.speed(), 1);
+    press(&mut app, &[KeyCode::Char('-'), KeyCode::Down]);
+    assert_eq!(app.speed(), 1);
+    press(&mut app, &[KeyCode::Char('+'), KeyCode::Up]);
+    assert_eq!(app.speed(), 4);
+    press(&mut app, &[KeyCode::Char('+'); 64]);
+    assert_eq!(app.speed(), MAX_SPEED);
+    press(&mut app, &[KeyCode::Char('-')]);
+    assert_eq!(app.speed(), MAX_SPEED / 2);
+
+    // 重新开始时保留速度
+    press(&mut app, &[KeyCode::Char('s'), KeyCode::Char('r')]);
+    assert_eq!((app.speed(), app.interpreter().steps()), (MAX_SPEED / 2, 0));
+}
+
+#[test]
+fn input_is_fed_with_a_newline() {
+    let mut app = App::new(b",.,.,.".to_vec()).unwrap();
+    press(&mut app, &[KeyCode::Char(' ')]);
+    app.tick().unwrap();
+    assert!(!app.is_playing());
+    assert_eq!(app.status(), "Waiting for input, press i");
+
+    // 输入时按键是文字而不是命令
+    press(&mut app, &[KeyCode::Char('i')]);
+    press(&mut app, &chars("hq"));
+    press(&mut app, &[KeyCode::Backspace, KeyCode::Char('i'), KeyCode::Enter]);
+    assert_eq!(app.interpreter().input().iter().copied().collect::<Vec<u8>>(), b"hi\n");
+    assert_eq!(app.status(), "Paused");
+
+    // Esc 放弃输入
+    press(&mut app, &[KeyCode::Char('i'), KeyCode::Char('x'), KeyCode::Esc]);
+    assert_eq!(app.interpreter().input().len(), 3);
+
+    press(&mut app, &chars("++++ "));
+    app.tick().unwrap();
+    assert_eq!(app.output(), b"hi\n");
+    assert_eq!(app.status(), "Halted");
+    assert!(!app.is_playing());
+    // 结束后不能再播放
+    press(&mut app, &[KeyCode::Char(' ')]);
+    assert!(!app.is_playing());
+}
+
+#[test]
+fn pauses_at_debug_instructions() {
+    let mut app = App::new(b"+#+#+".to_vec()).unwrap();
+    press(&mut app, &chars("++++ "));
+    app.tick().unwrap();
+    assert_eq!(app.status(), "Paused at #");
+    assert!(!app.is_playing());
+    assert_eq!(app.interpreter().tape()[0], 1);
+
+    press(&mut app, &[KeyCode::Char(' ')]);
+    app.tick().unwrap();
+    assert_eq!(app.status(), "Paused at #");
+    assert_eq!(app.interpreter().tape()[0], 2);
+    press(&mut app, &[KeyCode::Char(' ')]);
+    app.tick().unwrap();
+    assert_eq!(app.status(), "Halted");
+    assert_eq!(app.interpreter().tape()[0], 3);
+}
+
+#[test]
+fn draws_source_tape_output_and_status() {
+    let mut app = App::new(b"++++++++[>++++++++<-]>+.\n,".to_vec()).unwrap();
+    press(&mut app, &[KeyCode::Char('m'), KeyCode::Char('m')]);
+    press(&mut app, &[KeyCode::Char('+'); 8]);
+    press(&mut app, &[KeyCode::Char(' ')]);
+    app.tick().unwrap();
+    press(&mut app, &[KeyCode::Char('i')]);
+    press(&mut app, &chars("ok"));
+
+    let mut terminal = Terminal::new(TestBackend::new(100, 24)).unwrap();
+    terminal.draw(|frame| app.draw(frame)).unwrap();
+    let screen = terminal.backend().to_string();
+    for text in [
+        " Source  #10 ",
+        "++++++++[>++++++++<-]>+.",
+        " Tape  pointer 1 ",
+        " \\x00    A \\x00",
+        "│A ",
+        " Output ",
+        "> ok_",
+        " Waiting for input, press i | step 45 | speed 256/tick",
+    ] {
+        assert!(screen.contains(text), "{:?} not in\n{}", text, screen);
+    }
+}